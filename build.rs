@@ -0,0 +1,6 @@
+fn main() {
+    // Declares the `loom` cfg of the model checks, in the single-colon form
+    // older Cargo versions accept.
+    println!("cargo:rustc-check-cfg=cfg(loom)");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,276 @@
+use crate::coop;
+use crate::deadlock::{Hold, Wait};
+use crate::queue::{Node, NodeState};
+use crate::time::{self, Sleep};
+use crate::{AcquireError, Enqueued, Permits, SemaphoreInner, SemaphorePermit};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Instant;
+
+/// Waits for permits without wrapping them in a guard.
+#[derive(Debug)]
+pub(crate) struct RawAcquire<'a> {
+    inner: &'a SemaphoreInner,
+    min: Permits,
+    max: Permits,
+    priority: u8,
+    deadline: Option<Instant>,
+    acquired: Permits,
+    node: Option<Arc<Node>>,
+    sleep: Option<Sleep>,
+    wait: Wait,
+}
+
+impl<'a> RawAcquire<'a> {
+    pub fn new(inner: &'a SemaphoreInner, count: Permits) -> Self {
+        Self::range(inner, count, count)
+    }
+
+    /// Waits for at least `min` permits, taking up to `max` if they are available.
+    pub fn range(inner: &'a SemaphoreInner, min: Permits, max: Permits) -> Self {
+        Self {
+            inner,
+            min,
+            max,
+            priority: 0,
+            deadline: None,
+            acquired: 0,
+            node: None,
+            sleep: None,
+            wait: Wait::none(),
+        }
+    }
+
+    /// Queues ahead of waiters with a lower priority.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Gives up waiting with a `Timeout` error at `deadline`.
+    pub fn until(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+}
+
+impl RawAcquire<'_> {
+    /// Gives up waiting once the deadline or the default timeout has passed.
+    fn poll_timeout(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), AcquireError>> {
+        let Some(sleep) = &mut self.sleep else {
+            return Poll::Pending;
+        };
+        ready!(Pin::new(sleep).poll(cx));
+
+        self.sleep = None;
+        self.wait = Wait::none();
+        if let Some(node) = self.node.take() {
+            // Permits granted in the meantime are returned by the cancel.
+            self.inner.cancel(&node);
+        }
+        Poll::Ready(Err(AcquireError::Timeout))
+    }
+
+    fn poll_acquire(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), AcquireError>> {
+        let node = match &self.node {
+            Some(node) => node,
+            None => match self.inner.acquire_or_enqueue(
+                self.min,
+                self.max,
+                self.priority,
+                cx.waker(),
+            )? {
+                Enqueued::Acquired(n) => {
+                    self.acquired = n;
+                    return Poll::Ready(Ok(()));
+                }
+                Enqueued::Queued(node) => {
+                    // The default timeout only starts once the task has to wait.
+                    let timeout = self
+                        .inner
+                        .config
+                        .timeout
+                        .and_then(|timeout| time::now().checked_add(timeout));
+                    let deadline = match (self.deadline, timeout) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                    self.sleep = deadline.map(Sleep::until);
+                    self.node = Some(node);
+                    self.wait = Wait::new(self.inner, cx.waker(), self.min);
+                    return self.poll_timeout(cx);
+                }
+            },
+        };
+
+        let mut state = node.state.lock().unwrap();
+        match &mut *state {
+            NodeState::Waiting(waker) => {
+                if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+                    *waker = Some(cx.waker().clone());
+                }
+                drop(state);
+                self.poll_timeout(cx)
+            }
+            NodeState::Granted(n) => {
+                let n = *n;
+                drop(state);
+                self.node = None;
+                self.wait = Wait::none();
+                // The permits may come from a holder that panicked while queued.
+                if self.inner.is_poisoned() {
+                    self.inner.release(n);
+                    return Poll::Ready(Err(AcquireError::Poisoned));
+                }
+                self.acquired = n;
+                Poll::Ready(Ok(()))
+            }
+            NodeState::Closed => {
+                drop(state);
+                self.node = None;
+                self.wait = Wait::none();
+                Poll::Ready(Err(AcquireError::Closed))
+            }
+        }
+    }
+}
+
+impl Future for RawAcquire<'_> {
+    type Output = Result<(), AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Takes from the task budget so a hot loop of acquires still yields.
+        let budget = ready!(coop::poll_proceed(cx));
+        let output = ready!(self.poll_acquire(cx));
+        budget.made_progress();
+        Poll::Ready(output)
+    }
+}
+
+impl Drop for RawAcquire<'_> {
+    fn drop(&mut self) {
+        if let Some(node) = self.node.take() {
+            self.inner.cancel(&node);
+        }
+    }
+}
+
+/// Future returned by [`Semaphore::acquire`], [`Semaphore::acquire_many`],
+/// [`Semaphore::acquire_with_priority`] and [`Semaphore::acquire_at_least`].
+///
+/// Resolves to a [`SemaphorePermit`] once the requested permits are acquired.
+/// The future is `Unpin`, so it can be stored in a struct and polled without boxing.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::{Acquire, Permits, Semaphore};
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+///
+/// struct Gated<'a> {
+///     acquire: Acquire<'a>,
+/// }
+///
+/// impl Future for Gated<'_> {
+///     type Output = Permits;
+///
+///     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Permits> {
+///         Pin::new(&mut self.acquire)
+///             .poll(cx)
+///             .map(|permit| permit.unwrap().num_permits())
+///     }
+/// }
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(2);
+/// assert_eq!(Gated { acquire: s.acquire_many(2) }.await, 2);
+/// # });
+/// ```
+///
+/// With the `tokio` feature, each acquire takes from the task's cooperative
+/// budget, so a task that keeps acquiring free permits still yields to the
+/// scheduler once the budget is used up.
+///
+/// # Cancel safety
+///
+/// Dropping the future gives up its place in the queue. Permits that were
+/// already handed to it but not yet returned by a poll go back to the
+/// semaphore, so acquiring inside `select!` never loses permits.
+///
+/// ```
+/// use async_sema::Semaphore;
+/// use std::time::Duration;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(1);
+///
+/// tokio::select! {
+///     _ = s.acquire_many(2) => unreachable!(),
+///     _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+/// }
+/// assert_eq!(s.available_permits(), 1);
+/// assert_eq!(s.num_waiters(), 0);
+///
+/// let mut acquire = s.acquire_many(2);
+/// assert!(futures::poll!(&mut acquire).is_pending());
+///
+/// // The permits are granted to the queued future, which is then dropped.
+/// s.add_permits(1);
+/// assert_eq!(s.available_permits(), 0);
+/// drop(acquire);
+/// assert_eq!(s.available_permits(), 2);
+/// # });
+/// ```
+///
+/// [`Semaphore::acquire`]: crate::Semaphore::acquire
+/// [`Semaphore::acquire_many`]: crate::Semaphore::acquire_many
+/// [`Semaphore::acquire_with_priority`]: crate::Semaphore::acquire_with_priority
+/// [`Semaphore::acquire_at_least`]: crate::Semaphore::acquire_at_least
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+#[derive(Debug)]
+pub struct Acquire<'a> {
+    raw: RawAcquire<'a>,
+}
+
+impl<'a> Acquire<'a> {
+    pub(crate) fn new(inner: &'a SemaphoreInner, count: Permits) -> Self {
+        Self {
+            raw: RawAcquire::new(inner, count),
+        }
+    }
+
+    pub(crate) fn range(inner: &'a SemaphoreInner, min: Permits, max: Permits) -> Self {
+        Self {
+            raw: RawAcquire::range(inner, min, max),
+        }
+    }
+
+    pub(crate) fn with_priority(self, priority: u8) -> Self {
+        Self {
+            raw: self.raw.with_priority(priority),
+        }
+    }
+
+    pub(crate) fn until(self, deadline: Instant) -> Self {
+        Self {
+            raw: self.raw.until(deadline),
+        }
+    }
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<SemaphorePermit<'a>, AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        ready!(Pin::new(&mut self.raw).poll(cx))?;
+        Poll::Ready(Ok(SemaphorePermit {
+            inner: self.raw.inner,
+            permits: self.raw.acquired,
+            hold: Hold::new(self.raw.inner, cx.waker(), self.raw.acquired),
+        }))
+    }
+}
@@ -0,0 +1,101 @@
+use event_listener::Event;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A semaphore with a single permit.
+///
+/// It behaves like `Semaphore::new(1)`, but acquiring and releasing only flip
+/// a flag instead of going through the weighted permit count.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::BinarySemaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = BinarySemaphore::new();
+///
+/// let guard = s.acquire().await;
+/// assert!(s.try_acquire().is_none());
+///
+/// drop(guard);
+/// assert!(s.try_acquire().is_some());
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct BinarySemaphore {
+    locked: AtomicBool,
+    event: Event,
+}
+
+impl BinarySemaphore {
+    /// Creates a new binary semaphore with its permit available.
+    #[cfg(not(loom))]
+    pub const fn new() -> BinarySemaphore {
+        BinarySemaphore {
+            locked: AtomicBool::new(false),
+            event: Event::new(),
+        }
+    }
+
+    /// Creates a new binary semaphore with its permit available.
+    // Loom's primitives cannot be created in a `const` context.
+    #[cfg(loom)]
+    pub fn new() -> BinarySemaphore {
+        BinarySemaphore {
+            locked: AtomicBool::new(false),
+            event: Event::new(),
+        }
+    }
+
+    /// Attempts to get the permit.
+    ///
+    /// Returns `None` if the permit is currently held.
+    pub fn try_acquire(&self) -> Option<BinarySemaphoreGuard<'_>> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| BinarySemaphoreGuard { sema: self })
+    }
+
+    /// Waits for the permit.
+    pub async fn acquire(&self) -> BinarySemaphoreGuard<'_> {
+        loop {
+            if let Some(guard) = self.try_acquire() {
+                return guard;
+            }
+
+            let listener = self.event.listen();
+            if let Some(guard) = self.try_acquire() {
+                return guard;
+            }
+            listener.await;
+        }
+    }
+
+    /// Returns whether the permit is currently held.
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Acquire)
+    }
+}
+
+impl Default for BinarySemaphore {
+    fn default() -> BinarySemaphore {
+        BinarySemaphore::new()
+    }
+}
+
+/// The permit of a [`BinarySemaphore`].
+///
+/// The permit is released when the guard is dropped.
+#[must_use = "the permit is released as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct BinarySemaphoreGuard<'a> {
+    sema: &'a BinarySemaphore,
+}
+
+impl Drop for BinarySemaphoreGuard<'_> {
+    fn drop(&mut self) {
+        self.sema.locked.store(false, Ordering::Release);
+        self.sema.event.notify(1);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::deadlock::Hold;
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit, TryAcquireError};
+use std::fmt;
+
+/// A semaphore counting a budget of bytes, such as the memory payloads in
+/// flight may take up.
+///
+/// Each acquire charges the length of a payload against the budget, and the
+/// guard gives back exactly what it was charged, even when part of it is
+/// released early. A payload larger than the whole budget is charged the
+/// whole budget, so it runs alone instead of waiting forever.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::BudgetSemaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let memory = BudgetSemaphore::new(64 * 1024);
+/// let payload = vec![0u8; 48 * 1024];
+///
+/// let mut charge = memory.acquire_bytes(payload.len()).await.unwrap();
+/// assert!(memory.try_acquire_bytes(32 * 1024).is_err());
+///
+/// // Half of the payload was written out.
+/// charge.release_bytes(24 * 1024);
+/// assert_eq!(memory.available_bytes(), 40 * 1024);
+///
+/// drop(charge);
+/// assert_eq!(memory.available_bytes(), 64 * 1024);
+/// # });
+/// ```
+pub struct BudgetSemaphore {
+    sema: Semaphore,
+}
+
+impl BudgetSemaphore {
+    /// Creates a budget of `bytes` bytes.
+    pub fn new(bytes: usize) -> BudgetSemaphore {
+        BudgetSemaphore::with_semaphore(Semaphore::new(bytes as Permits))
+    }
+
+    /// Creates a budget counting a byte for each permit of `sema`.
+    pub fn with_semaphore(sema: Semaphore) -> BudgetSemaphore {
+        BudgetSemaphore { sema }
+    }
+
+    /// Waits until `len` bytes fit in the budget, and charges them.
+    ///
+    /// A length larger than the whole budget waits for the whole budget.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::BudgetSemaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let memory = BudgetSemaphore::new(1024);
+    ///
+    /// let huge = memory.acquire_bytes(4096).await.unwrap();
+    /// assert_eq!(huge.bytes(), 4096);
+    /// assert_eq!(memory.available_bytes(), 0);
+    ///
+    /// drop(huge);
+    /// assert_eq!(memory.available_bytes(), 1024);
+    /// # });
+    /// ```
+    pub async fn acquire_bytes(&self, len: usize) -> Result<BudgetPermit<'_>, AcquireError> {
+        let permit = self.sema.acquire_many(self.charge(len)).await?;
+        Ok(BudgetPermit { permit, bytes: len })
+    }
+
+    /// Charges `len` bytes if they fit in the budget right away.
+    ///
+    /// A length larger than the whole budget needs the whole budget.
+    pub fn try_acquire_bytes(&self, len: usize) -> Result<BudgetPermit<'_>, TryAcquireError> {
+        let charge = self.charge(len);
+        self.sema.inner.try_acquire_exact(charge)?;
+        let permit = SemaphorePermit {
+            inner: &self.sema.inner,
+            permits: charge,
+            hold: Hold::none(),
+        };
+        Ok(BudgetPermit { permit, bytes: len })
+    }
+
+    /// Returns the number of bytes left in the budget.
+    pub fn available_bytes(&self) -> Permits {
+        self.sema.available_permits()
+    }
+
+    /// Returns the size of the whole budget in bytes.
+    pub fn budget(&self) -> Permits {
+        self.sema.inner.capacity()
+    }
+
+    /// Returns the semaphore holding a permit for each byte.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+
+    /// Returns the permits to charge for `len` bytes.
+    fn charge(&self, len: usize) -> Permits {
+        (len as Permits).min(self.budget())
+    }
+}
+
+impl fmt::Debug for BudgetSemaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetSemaphore")
+            .field("available", &self.available_bytes())
+            .field("budget", &self.budget())
+            .finish()
+    }
+}
+
+/// Bytes charged against a [`BudgetSemaphore`].
+///
+/// Dropping the guard gives the bytes back to the budget.
+#[must_use = "the bytes are released as soon as the guard is dropped"]
+pub struct BudgetPermit<'a> {
+    permit: SemaphorePermit<'a>,
+    /// The bytes asked for, which may exceed the permits held when they
+    /// were capped at the budget.
+    bytes: usize,
+}
+
+impl BudgetPermit<'_> {
+    /// Returns the number of bytes held by this guard.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Gives `len` of the held bytes back to the budget right away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this guard holds fewer than `len` bytes.
+    pub fn release_bytes(&mut self, len: usize) {
+        assert!(len <= self.bytes, "releasing more bytes than held");
+        self.bytes -= len;
+        let held = self.permit.num_permits();
+        self.permit
+            .release(held - (self.bytes as Permits).min(held));
+    }
+}
+
+impl fmt::Debug for BudgetPermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BudgetPermit")
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{Config, Permits, Semaphore, SemaphoreInner};
+use std::time::Duration;
+
+/// Builds a [`Semaphore`] with non-default behavior.
+///
+/// Created by [`Semaphore::builder`].
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+///
+/// let s = Semaphore::builder().permits(8).max_permits(16).fair(true).build();
+///
+/// assert_eq!(s.available_permits(), 8);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SemaphoreBuilder {
+    permits: Permits,
+    config: Config,
+}
+
+impl SemaphoreBuilder {
+    /// Creates a builder for a semaphore without permits.
+    pub fn new() -> SemaphoreBuilder {
+        SemaphoreBuilder {
+            permits: 0,
+            config: Config::new(),
+        }
+    }
+
+    /// Sets the number of permits the semaphore starts with.
+    pub fn permits(mut self, n: Permits) -> SemaphoreBuilder {
+        self.permits = n;
+        self
+    }
+
+    /// Sets the maximum number of permits the semaphore may ever hold.
+    ///
+    /// Adding permits beyond the maximum panics, see [`Semaphore::bounded`].
+    pub fn max_permits(mut self, n: Permits) -> SemaphoreBuilder {
+        self.config.max = n;
+        self
+    }
+
+    /// Sets whether permits are granted strictly in arrival order.
+    ///
+    /// By default a waiting task that needs more permits than are available
+    /// lets smaller requests behind it go first, and new acquires can take
+    /// free permits even while others are queued. In fair mode neither
+    /// happens: a queued request blocks everyone who arrived after it, and
+    /// `try_acquire` fails while any task is queued.
+    ///
+    /// Without fair mode, a request at the head of the queue that has been
+    /// overtaken for more than a millisecond still blocks the queue until it
+    /// is served, so large requests are never starved by small ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{AcquireError, Semaphore, TryAcquireError};
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::builder().permits(1).fair(true).build();
+    ///
+    /// let held = s.acquire().await.unwrap();
+    /// let big = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.acquire_many(2).await.map(drop) }
+    /// });
+    /// while s.num_waiters() == 0 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    ///
+    /// // The free permit is kept for the request that queued first.
+    /// s.add_permits(1);
+    /// assert_eq!(s.try_acquire(), Err(TryAcquireError::NoPermits));
+    /// let small = s.acquire_timeout(1, Duration::from_millis(10)).await;
+    /// assert_eq!(small.unwrap_err(), AcquireError::Timeout);
+    ///
+    /// drop(held);
+    /// big.await.unwrap().unwrap();
+    /// # });
+    /// ```
+    pub fn fair(mut self, fair: bool) -> SemaphoreBuilder {
+        self.config.fair = fair;
+        self
+    }
+
+    /// Sets the order in which waiting tasks are served.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, WakeOrder};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::builder().wake_order(WakeOrder::Lifo).build();
+    ///
+    /// let mut tasks = Vec::new();
+    /// for id in 0..3 {
+    ///     let task = s.clone();
+    ///     tasks.push(tokio::spawn(async move {
+    ///         task.acquire_unguarded().await.unwrap();
+    ///         id
+    ///     }));
+    ///     while s.num_waiters() <= id {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    /// }
+    ///
+    /// // The task that started waiting last is served first.
+    /// s.add_permits(1);
+    /// assert_eq!(tasks.pop().unwrap().await.unwrap(), 2);
+    /// # s.add_permits(2);
+    /// # });
+    /// ```
+    pub fn wake_order(mut self, order: WakeOrder) -> SemaphoreBuilder {
+        self.config.order = order;
+        self
+    }
+
+    /// Sets how long any acquire may wait before failing.
+    ///
+    /// Every acquire future that has to queue gives up with a `Timeout` error
+    /// once it has waited for `timeout`, as if it had been wrapped in
+    /// [`Semaphore::acquire_timeout`]. Acquires that succeed right away are
+    /// not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{AcquireError, Semaphore};
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::builder()
+    ///     .permits(1)
+    ///     .acquire_timeout(Duration::from_millis(10))
+    ///     .build();
+    ///
+    /// let guard = s.acquire().await.unwrap();
+    /// assert_eq!(s.acquire().await.unwrap_err(), AcquireError::Timeout);
+    /// assert_eq!(s.num_waiters(), 0);
+    /// # drop(guard);
+    /// # });
+    /// ```
+    pub fn acquire_timeout(mut self, timeout: Duration) -> SemaphoreBuilder {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether a panic while holding permits poisons the semaphore.
+    ///
+    /// When enabled, a guard dropped while its thread is panicking marks the
+    /// semaphore as poisoned, as the state it protects may be inconsistent.
+    /// Acquires then fail with a `Poisoned` error until
+    /// [`Semaphore::clear_poison`] is called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, TryAcquireError};
+    /// use std::panic::{catch_unwind, AssertUnwindSafe};
+    ///
+    /// let s = Semaphore::builder().permits(1).poison(true).build();
+    ///
+    /// let _ = catch_unwind(AssertUnwindSafe(|| {
+    ///     let _guard = s.try_acquire_upto(1).unwrap();
+    ///     panic!("corrupted the shared state");
+    /// }));
+    /// assert!(s.is_poisoned());
+    /// assert_eq!(s.try_acquire(), Err(TryAcquireError::Poisoned));
+    ///
+    /// s.clear_poison();
+    /// assert!(s.try_acquire().is_ok());
+    /// ```
+    pub fn poison(mut self, poison: bool) -> SemaphoreBuilder {
+        self.config.poison = poison;
+        self
+    }
+
+    /// Creates the semaphore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initial permits exceed the maximum.
+    pub fn build(self) -> Semaphore {
+        assert!(
+            self.permits <= self.config.max,
+            "initial permits exceed the semaphore maximum of {}",
+            self.config.max
+        );
+        Semaphore::from_inner(SemaphoreInner::with_config(self.permits, self.config))
+    }
+}
+
+/// The order in which a semaphore serves waiting tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakeOrder {
+    /// The task that has waited longest is served first.
+    #[default]
+    Fifo,
+    /// The task that started waiting most recently is served first.
+    ///
+    /// This keeps recently active tasks hot and lets old waiters time out.
+    Lifo,
+}
+
+impl Default for SemaphoreBuilder {
+    fn default() -> SemaphoreBuilder {
+        SemaphoreBuilder::new()
+    }
+}
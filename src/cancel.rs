@@ -0,0 +1,91 @@
+use event_listener::Event;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+
+#[derive(Debug, Default)]
+struct TokenInner {
+    cancelled: AtomicBool,
+    event: Event,
+}
+
+/// A token for signalling cancellation to pending acquires.
+///
+/// Clones share the same state, so cancelling any of them cancels all.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::CancellationToken;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let token = CancellationToken::new();
+/// let waiter = tokio::spawn({
+///     let token = token.clone();
+///     async move { token.cancelled().await }
+/// });
+///
+/// token.cancel();
+/// waiter.await.unwrap();
+/// assert!(token.is_cancelled());
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that has not been cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels the token, waking every task waiting on it.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        self.inner.event.notify(usize::MAX);
+    }
+
+    /// Returns whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Waits until the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            if self.is_cancelled() {
+                return;
+            }
+
+            let listener = self.inner.event.listen();
+            if self.is_cancelled() {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
+
+/// Runs `fut` until it completes or `token` is cancelled, whichever comes first.
+///
+/// Returns `None` if the token was cancelled first, including when it was
+/// already cancelled before `fut` was polled.
+pub(crate) async fn until_cancelled<F: Future>(
+    token: &CancellationToken,
+    fut: F,
+) -> Option<F::Output> {
+    let mut fut = pin!(fut);
+    let mut cancelled = pin!(token.cancelled());
+
+    poll_fn(|cx| {
+        if let Poll::Ready(()) = cancelled.as_mut().poll(cx) {
+            return Poll::Ready(None);
+        }
+        fut.as_mut().poll(cx).map(Some)
+    })
+    .await
+}
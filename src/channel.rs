@@ -0,0 +1,362 @@
+//! A bounded multi-producer, multi-consumer channel.
+//!
+//! Both ends are backed by semaphores: senders take a permit of capacity for
+//! each value, and receivers take a permit for each queued value. A full
+//! channel therefore applies backpressure to its senders, who wait in the
+//! order the semaphore serves them.
+//!
+//! # Examples
+//!
+//! ```
+//! use async_sema::channel;
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let (tx, rx) = channel::bounded(2);
+//!
+//! let producer = tokio::spawn(async move {
+//!     for i in 0..10 {
+//!         tx.send(i).await.unwrap();
+//!     }
+//! });
+//!
+//! let mut sum = 0;
+//! while let Ok(i) = rx.recv().await {
+//!     sum += i;
+//! }
+//! assert_eq!(sum, 45);
+//! producer.await.unwrap();
+//! # });
+//! ```
+
+use crate::{Permits, SemaphoreInner, TryAcquireError};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    /// One permit for each free slot, closed once all receivers are gone.
+    slots: SemaphoreInner,
+    /// One permit for each queued value, closed once all senders are gone.
+    items: SemaphoreInner,
+    capacity: usize,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+}
+
+impl<T> Shared<T> {
+    fn push(&self, value: T) {
+        self.queue.lock().unwrap().push_back(value);
+        self.items.release(1);
+    }
+
+    fn pop(&self) -> Option<T> {
+        let value = self.queue.lock().unwrap().pop_front()?;
+        self.slots.release(1);
+        Some(value)
+    }
+}
+
+/// Creates a channel that holds up to `capacity` values.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be positive");
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        slots: SemaphoreInner::new(capacity as Permits),
+        items: SemaphoreInner::new(0),
+        capacity,
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+/// The sending half of a channel, created by [`bounded`].
+///
+/// Senders can be cloned to send from several tasks. Once all of them are
+/// dropped, receivers get the values still queued and then an error.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `value`, waiting for a free slot if the channel is full.
+    ///
+    /// Returns the value in an error if all receivers have been dropped.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.shared.slots.acquire(1).await {
+            Ok(()) => {
+                self.shared.push(value);
+                Ok(())
+            }
+            Err(_) => Err(SendError(value)),
+        }
+    }
+
+    /// Sends `value` if a slot is free right away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::channel::{self, TrySendError};
+    ///
+    /// let (tx, rx) = channel::bounded(1);
+    ///
+    /// assert!(tx.try_send(1).is_ok());
+    /// assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+    ///
+    /// drop(rx);
+    /// assert_eq!(tx.try_send(3), Err(TrySendError::Closed(3)));
+    /// ```
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self.shared.slots.try_acquire_exact(1) {
+            Ok(()) => {
+                self.shared.push(value);
+                Ok(())
+            }
+            Err(TryAcquireError::NoPermits) => Err(TrySendError::Full(value)),
+            Err(_) => Err(TrySendError::Closed(value)),
+        }
+    }
+
+    /// Returns the number of values waiting to be received.
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().len()
+    }
+
+    /// Returns whether no values are waiting to be received.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values the channel can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Returns whether all receivers have been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.shared.slots.is_closed()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.items.close();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// The receiving half of a channel, created by [`bounded`].
+///
+/// Receivers can be cloned, and each value is received by only one of them.
+/// Once all of them are dropped, sending fails.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next value, waiting for one if the channel is empty.
+    ///
+    /// Returns an error once all senders have been dropped and no values
+    /// are left.
+    pub async fn recv(&self) -> Result<T, RecvError> {
+        // Once the senders are gone the semaphore is closed, and the values
+        // left are taken without a permit.
+        let _ = self.shared.items.acquire(1).await;
+        self.shared.pop().ok_or(RecvError)
+    }
+
+    /// Receives the next value if one is queued.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::channel::{self, TryRecvError};
+    ///
+    /// let (tx, rx) = channel::bounded(1);
+    ///
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    /// tx.try_send(1).unwrap();
+    /// drop(tx);
+    ///
+    /// assert_eq!(rx.try_recv(), Ok(1));
+    /// assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+    /// ```
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        if let Err(TryAcquireError::NoPermits) = self.shared.items.try_acquire_exact(1) {
+            return Err(TryRecvError::Empty);
+        }
+        self.shared.pop().ok_or(TryRecvError::Closed)
+    }
+
+    /// Returns the number of values waiting to be received.
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().len()
+    }
+
+    /// Returns whether no values are waiting to be received.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of values the channel can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Returns whether all senders have been dropped.
+    ///
+    /// Values sent before may still be waiting to be received.
+    pub fn is_closed(&self) -> bool {
+        self.shared.items.is_closed()
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.shared.receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.slots.close();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// Error returned by [`Sender::send`] when all receivers have been dropped.
+///
+/// Holds the value that could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+/// Error returned by [`Sender::try_send`].
+///
+/// Holds the value that could not be sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The channel is full.
+    Full(T),
+    /// All receivers have been dropped.
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the value that could not be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Closed(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("Full(..)"),
+            TrySendError::Closed(_) => f.write_str("Closed(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.write_str("channel full"),
+            TrySendError::Closed(_) => f.write_str("channel closed"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+/// Error returned by [`Receiver::recv`] once the channel is closed and empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl Error for RecvError {}
+
+/// Error returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// No value is queued right now.
+    Empty,
+    /// All senders have been dropped and no values are left.
+    Closed,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("channel empty"),
+            TryRecvError::Closed => f.write_str("channel closed"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
@@ -0,0 +1,33 @@
+//! Cooperative scheduling with tokio's task budget.
+//!
+//! With the `tokio` feature, acquiring permits consumes budget from the
+//! current tokio task, so a loop over an uncontended semaphore still yields
+//! to the scheduler now and then. Without the feature this does nothing.
+
+use std::task::{Context, Poll};
+
+/// Budget taken for one poll, given back unless progress is reported.
+pub(crate) struct Budget {
+    #[cfg(feature = "tokio")]
+    restore: tokio::task::coop::RestoreOnPending,
+}
+
+impl Budget {
+    /// Keeps the consumed budget, as the poll completed.
+    pub fn made_progress(&self) {
+        #[cfg(feature = "tokio")]
+        self.restore.made_progress();
+    }
+}
+
+/// Returns `Pending` once the current task has used up its budget.
+pub(crate) fn poll_proceed(cx: &mut Context<'_>) -> Poll<Budget> {
+    #[cfg(feature = "tokio")]
+    return tokio::task::coop::poll_proceed(cx).map(|restore| Budget { restore });
+
+    #[cfg(not(feature = "tokio"))]
+    {
+        let _ = cx;
+        Poll::Ready(Budget {})
+    }
+}
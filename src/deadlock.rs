@@ -0,0 +1,341 @@
+//! Detection of tasks waiting on each other across semaphores.
+//!
+//! With the `deadlock-detection` feature every permit guard remembers the
+//! task that awaited it, and every queued acquire remembers the task waiting.
+//! A task is identified by its waker. [`deadlocks`] then looks for cycles of
+//! tasks that wait for permits held by the next task in the cycle, where a
+//! task waiting for permits it could only get back from itself is a cycle
+//! of its own. Without the feature the types here are empty and tracking
+//! compiles to nothing.
+
+#[cfg(feature = "deadlock-detection")]
+mod imp {
+    use crate::{Permits, Semaphore, SemaphoreInner};
+    use std::fmt;
+    use std::sync::Mutex;
+    use std::task::Waker;
+
+    static REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+
+    /// Identifies a semaphore by the address of its shared state.
+    type SemKey = usize;
+
+    fn key(inner: &SemaphoreInner) -> SemKey {
+        inner as *const SemaphoreInner as usize
+    }
+
+    #[derive(Debug)]
+    struct Task {
+        id: u64,
+        waker: Waker,
+    }
+
+    #[derive(Debug)]
+    struct Held {
+        task: u64,
+        sem: SemKey,
+        permits: Permits,
+    }
+
+    #[derive(Debug)]
+    struct Waiting {
+        id: u64,
+        task: u64,
+        sem: SemKey,
+        /// Whether the task holds so many permits of the semaphore that the
+        /// rest of its capacity cannot satisfy the request.
+        on_itself: bool,
+    }
+
+    #[derive(Debug)]
+    struct Registry {
+        next: u64,
+        tasks: Vec<Task>,
+        held: Vec<Held>,
+        waiting: Vec<Waiting>,
+    }
+
+    impl Registry {
+        const fn new() -> Self {
+            Self {
+                next: 0,
+                tasks: Vec::new(),
+                held: Vec::new(),
+                waiting: Vec::new(),
+            }
+        }
+
+        fn next_id(&mut self) -> u64 {
+            self.next += 1;
+            self.next
+        }
+
+        /// Returns the id of the task woken by `waker`, registering it if needed.
+        fn task(&mut self, waker: &Waker) -> u64 {
+            if let Some(task) = self.tasks.iter().find(|t| t.waker.will_wake(waker)) {
+                return task.id;
+            }
+            let id = self.next_id();
+            self.tasks.push(Task {
+                id,
+                waker: waker.clone(),
+            });
+            id
+        }
+
+        /// Forgets tasks that neither hold permits nor wait for any.
+        fn prune(&mut self) {
+            let (held, waiting) = (&self.held, &self.waiting);
+            self.tasks.retain(|t| {
+                held.iter().any(|h| h.task == t.id) || waiting.iter().any(|w| w.task == t.id)
+            });
+        }
+
+        fn hold(&mut self, task: u64, sem: SemKey, permits: Permits) {
+            match self
+                .held
+                .iter_mut()
+                .find(|h| h.task == task && h.sem == sem)
+            {
+                Some(held) => held.permits += permits,
+                None => self.held.push(Held { task, sem, permits }),
+            }
+        }
+
+        fn unhold(&mut self, task: u64, sem: SemKey, permits: Permits) {
+            if let Some(i) = self
+                .held
+                .iter()
+                .position(|h| h.task == task && h.sem == sem)
+            {
+                self.held[i].permits = self.held[i].permits.saturating_sub(permits);
+                if self.held[i].permits == 0 {
+                    self.held.swap_remove(i);
+                    self.prune();
+                }
+            }
+        }
+
+        /// Returns the tasks that `task` waits on, with the semaphore involved.
+        fn edges(&self, task: u64) -> impl Iterator<Item = (SemKey, u64)> + '_ {
+            self.waiting
+                .iter()
+                .filter(move |w| w.task == task)
+                .flat_map(move |w| {
+                    self.held
+                        .iter()
+                        .filter(move |h| h.sem == w.sem && h.task != task)
+                        .map(move |h| (w.sem, h.task))
+                })
+        }
+
+        /// Extends `path` with every cycle back to `path[0]` through tasks
+        /// with a greater id, so each cycle is found once.
+        fn cycles(&self, path: &mut Vec<(u64, SemKey)>, task: u64, found: &mut Vec<Deadlock>) {
+            let start = path.first().map_or(task, |&(t, _)| t);
+            for (sem, next) in self.edges(task) {
+                path.push((task, sem));
+                if next == start {
+                    found.push(Deadlock {
+                        tasks: path.iter().map(|&(t, _)| t).collect(),
+                        semaphores: path.iter().map(|&(_, s)| s).collect(),
+                    });
+                } else if next > start && !path.iter().any(|&(t, _)| t == next) {
+                    self.cycles(path, next, found);
+                }
+                path.pop();
+            }
+        }
+    }
+
+    /// A cycle of tasks that wait for permits held by each other.
+    ///
+    /// Returned by [`deadlocks`]. Tasks are numbered in the order they were
+    /// first seen holding or waiting for permits.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Deadlock {
+        tasks: Vec<u64>,
+        semaphores: Vec<SemKey>,
+    }
+
+    impl Deadlock {
+        /// Returns the tasks in the cycle, each waiting for permits held by the
+        /// next one, and the last waiting for the first.
+        pub fn tasks(&self) -> &[u64] {
+            &self.tasks
+        }
+
+        /// Returns whether a task in the cycle waits on `semaphore`.
+        pub fn involves(&self, semaphore: &Semaphore) -> bool {
+            self.semaphores.contains(&key(&semaphore.inner))
+        }
+    }
+
+    impl fmt::Display for Deadlock {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let n = self.tasks.len();
+            for (i, (task, sem)) in self.tasks.iter().zip(&self.semaphores).enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                let holder = self.tasks[(i + 1) % n];
+                write!(
+                    f,
+                    "task {task} waits on semaphore {sem:#x} held by task {holder}"
+                )?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Returns the probable deadlocks among all semaphores.
+    ///
+    /// A cycle is only probable, as a task in it may still make progress, for
+    /// example when other permits are released or an acquire times out.
+    /// Permits are attributed to the task that awaited them, even once the
+    /// guard has moved to another task, and permits taken with `try_acquire`
+    /// or a blocking acquire are not considered.
+    pub fn deadlocks() -> Vec<Deadlock> {
+        let registry = REGISTRY.lock().unwrap();
+        let mut found: Vec<_> = registry
+            .waiting
+            .iter()
+            .filter(|w| w.on_itself)
+            .map(|w| Deadlock {
+                tasks: vec![w.task],
+                semaphores: vec![w.sem],
+            })
+            .collect();
+        for task in &registry.tasks {
+            registry.cycles(&mut Vec::new(), task.id, &mut found);
+        }
+        found
+    }
+
+    /// The task holding the permits of a guard.
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct Hold {
+        task: Option<(u64, SemKey)>,
+    }
+
+    impl Hold {
+        /// Records `permits` of `inner` as held by the task woken by `waker`.
+        pub fn new(inner: &SemaphoreInner, waker: &Waker, permits: Permits) -> Self {
+            if permits == 0 {
+                return Self::none();
+            }
+            let mut registry = REGISTRY.lock().unwrap();
+            let (task, sem) = (registry.task(waker), key(inner));
+            registry.hold(task, sem, permits);
+            Self {
+                task: Some((task, sem)),
+            }
+        }
+
+        /// Permits not attributed to any task.
+        pub fn none() -> Self {
+            Self { task: None }
+        }
+
+        pub fn release(&self, permits: Permits) {
+            if let (Some((task, sem)), true) = (self.task, permits > 0) {
+                REGISTRY.lock().unwrap().unhold(task, sem, permits);
+            }
+        }
+
+        /// Moves `permits` held by `other` over to this task.
+        pub fn take_from(&mut self, other: &Hold, permits: Permits) {
+            if permits == 0 {
+                return;
+            }
+            let mut registry = REGISTRY.lock().unwrap();
+            if let Some((task, sem)) = other.task {
+                registry.unhold(task, sem, permits);
+            }
+            if let Some((task, sem)) = self.task {
+                registry.hold(task, sem, permits);
+            }
+        }
+    }
+
+    /// Records the task waiting in a queued acquire, until dropped.
+    #[derive(Debug)]
+    pub(crate) struct Wait {
+        id: Option<u64>,
+    }
+
+    impl Wait {
+        /// Records the task woken by `waker` as waiting for `min` permits.
+        pub fn new(inner: &SemaphoreInner, waker: &Waker, min: Permits) -> Self {
+            let mut registry = REGISTRY.lock().unwrap();
+            let (task, sem) = (registry.task(waker), key(inner));
+            let held = registry
+                .held
+                .iter()
+                .find(|h| h.task == task && h.sem == sem)
+                .map_or(0, |h| h.permits);
+            let id = registry.next_id();
+            registry.waiting.push(Waiting {
+                id,
+                task,
+                sem,
+                on_itself: held > 0 && inner.capacity().saturating_sub(held) < min,
+            });
+            Self { id: Some(id) }
+        }
+
+        pub fn none() -> Self {
+            Self { id: None }
+        }
+    }
+
+    impl Drop for Wait {
+        fn drop(&mut self) {
+            if let Some(id) = self.id {
+                let mut registry = REGISTRY.lock().unwrap();
+                registry.waiting.retain(|w| w.id != id);
+                registry.prune();
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "deadlock-detection"))]
+mod imp {
+    use crate::{Permits, SemaphoreInner};
+    use std::task::Waker;
+
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct Hold;
+
+    impl Hold {
+        pub fn new(_inner: &SemaphoreInner, _waker: &Waker, _permits: Permits) -> Self {
+            Self
+        }
+
+        pub fn none() -> Self {
+            Self
+        }
+
+        pub fn release(&self, _permits: Permits) {}
+
+        pub fn take_from(&mut self, _other: &Hold, _permits: Permits) {}
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct Wait;
+
+    impl Wait {
+        pub fn new(_inner: &SemaphoreInner, _waker: &Waker, _min: Permits) -> Self {
+            Self
+        }
+
+        pub fn none() -> Self {
+            Self
+        }
+    }
+}
+
+#[cfg(feature = "deadlock-detection")]
+pub use imp::{deadlocks, Deadlock};
+pub(crate) use imp::{Hold, Wait};
@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::fmt;
+
+/// Error returned when waiting for permits fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// The permits could not be acquired before the timeout elapsed.
+    Timeout,
+    /// The acquire was cancelled through a [`CancellationToken`].
+    ///
+    /// [`CancellationToken`]: crate::CancellationToken
+    Cancelled,
+    /// A holder panicked while holding permits, see [`Semaphore::is_poisoned`].
+    ///
+    /// [`Semaphore::is_poisoned`]: crate::Semaphore::is_poisoned
+    Poisoned,
+    /// The limiter shed the acquire, as too many tasks were already waiting
+    /// in a [`Bulkhead`], or a [`CircuitBreaker`] is open.
+    ///
+    /// [`Bulkhead`]: crate::limit::Bulkhead
+    /// [`CircuitBreaker`]: crate::limit::CircuitBreaker
+    Rejected,
+}
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AcquireError::Closed => f.write_str("semaphore closed"),
+            AcquireError::Timeout => f.write_str("timed out waiting for permits"),
+            AcquireError::Cancelled => f.write_str("acquire cancelled"),
+            AcquireError::Poisoned => f.write_str("semaphore poisoned"),
+            AcquireError::Rejected => f.write_str("acquire rejected"),
+        }
+    }
+}
+
+impl Error for AcquireError {}
+
+/// Error returned when permits cannot be acquired immediately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TryAcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// Not enough permits are currently available.
+    NoPermits,
+    /// A holder panicked while holding permits, see [`Semaphore::is_poisoned`].
+    ///
+    /// [`Semaphore::is_poisoned`]: crate::Semaphore::is_poisoned
+    Poisoned,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => f.write_str("semaphore closed"),
+            TryAcquireError::NoPermits => f.write_str("no permits available"),
+            TryAcquireError::Poisoned => f.write_str("semaphore poisoned"),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
+
+/// Error returned when renewing a lease whose permits have already expired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaseExpired;
+
+impl fmt::Display for LeaseExpired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("lease expired")
+    }
+}
+
+impl Error for LeaseExpired {}
@@ -0,0 +1,100 @@
+use crate::{Acquire, AcquireError, Permits, Semaphore, SemaphorePermit};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Extension methods for running futures under a semaphore.
+///
+/// Implemented for every future.
+pub trait FutureExt: Future + Sized {
+    /// Waits for a permit of `sema` before polling this future, and holds
+    /// it until the future completes.
+    ///
+    /// The inner future is not polled at all until the permit is acquired.
+    /// Dropping the returned future drops the inner one, then releases the
+    /// permit, or gives up its place in the queue if it was still waiting.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{FutureExt, Semaphore};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(1);
+    ///
+    /// let free = async { s.available_permits() }.with_permit(&s).await;
+    /// assert_eq!(free, Ok(0));
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    ///
+    /// # Cancel safety
+    ///
+    /// ```
+    /// use async_sema::{FutureExt, Semaphore};
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(1);
+    ///
+    /// let slow = tokio::time::sleep(Duration::from_secs(60)).with_permit(&s);
+    /// assert!(tokio::time::timeout(Duration::from_millis(10), slow)
+    ///     .await
+    ///     .is_err());
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    fn with_permit(self, sema: &Semaphore) -> WithPermit<'_, Self> {
+        self.with_permits(sema, 1)
+    }
+
+    /// Waits for `count` permits of `sema` before polling this future, and
+    /// holds them until the future completes.
+    ///
+    /// See [`with_permit`](FutureExt::with_permit).
+    fn with_permits(self, sema: &Semaphore, count: Permits) -> WithPermit<'_, Self> {
+        WithPermit {
+            acquire: sema.acquire_many(count),
+            future: Box::pin(self),
+            permit: None,
+        }
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+/// Future returned by [`FutureExt::with_permit`] and
+/// [`FutureExt::with_permits`].
+///
+/// Resolves to the output of the inner future, or to the error of the
+/// acquire if no permit could be taken.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WithPermit<'a, F> {
+    acquire: Acquire<'a>,
+    // Dropped before the permit, so the inner future never runs without it.
+    future: Pin<Box<F>>,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl<F: Future> Future for WithPermit<'_, F> {
+    type Output = Result<F::Output, AcquireError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.permit.is_none() {
+            this.permit = Some(ready!(Pin::new(&mut this.acquire).poll(cx))?);
+        }
+        let output = ready!(this.future.as_mut().poll(cx));
+        this.permit = None;
+        Poll::Ready(Ok(output))
+    }
+}
+
+impl<F> fmt::Debug for WithPermit<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithPermit")
+            .field("acquired", &self.permit.is_some())
+            .finish()
+    }
+}
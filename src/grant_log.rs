@@ -0,0 +1,83 @@
+//! Recording of the order in which waiters are granted permits.
+//!
+//! With the `grant-log` feature every acquire is given a waiter id in the
+//! order it first polled, and each grant is logged. Without the feature the
+//! types here are empty and recording compiles to nothing.
+
+#[cfg(feature = "grant-log")]
+mod imp {
+    use crate::primitive::Mutex;
+    use crate::Permits;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Permits granted to a waiter, as recorded by [`Semaphore::take_grant_log`].
+    ///
+    /// [`Semaphore::take_grant_log`]: crate::Semaphore::take_grant_log
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Grant {
+        /// The waiter id, counting acquires from zero in the order they started.
+        pub waiter: u64,
+        /// The number of permits granted.
+        pub permits: Permits,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct WaiterId(u64);
+
+    #[derive(Debug)]
+    pub(crate) struct GrantLog {
+        next: AtomicU64,
+        grants: Mutex<Vec<Grant>>,
+    }
+
+    impl GrantLog {
+        pub fn new() -> Self {
+            Self {
+                next: AtomicU64::new(0),
+                grants: Mutex::new(Vec::new()),
+            }
+        }
+
+        pub fn waiter(&self) -> WaiterId {
+            WaiterId(self.next.fetch_add(1, Ordering::Relaxed))
+        }
+
+        pub fn record(&self, waiter: WaiterId, permits: Permits) {
+            self.grants.lock().unwrap().push(Grant {
+                waiter: waiter.0,
+                permits,
+            });
+        }
+
+        pub fn take(&self) -> Vec<Grant> {
+            std::mem::take(&mut *self.grants.lock().unwrap())
+        }
+    }
+}
+
+#[cfg(not(feature = "grant-log"))]
+mod imp {
+    use crate::Permits;
+
+    #[derive(Debug, Clone, Copy)]
+    pub(crate) struct WaiterId;
+
+    #[derive(Debug)]
+    pub(crate) struct GrantLog;
+
+    impl GrantLog {
+        pub fn new() -> Self {
+            Self
+        }
+
+        pub fn waiter(&self) -> WaiterId {
+            WaiterId
+        }
+
+        pub fn record(&self, _waiter: WaiterId, _permits: Permits) {}
+    }
+}
+
+#[cfg(feature = "grant-log")]
+pub use imp::Grant;
+pub(crate) use imp::{GrantLog, WaiterId};
@@ -0,0 +1,174 @@
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit, TryAcquireError};
+use std::fmt;
+
+/// A set of semaphores, one for each stage of a pipeline, where finishing
+/// at one stage hands the permits on to another.
+///
+/// By default every stage feeds the next one: dropping a permit of stage `i`
+/// moves it to stage `i + 1` instead of returning it, so the next stage
+/// gets a permit for each item the previous one finished. Permits of a
+/// stage that feeds no other return to it, as with a plain semaphore.
+/// [`feed`] reroutes a stage, for example back to the first one, so a fixed
+/// number of items circulates through the pipeline.
+///
+/// [`feed`]: SemaphoreGroup::feed
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::SemaphoreGroup;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// // Two items in flight through parse, process and write.
+/// let pipeline = SemaphoreGroup::new(&[2, 0, 0]).feed(2, 0);
+///
+/// let parsed = pipeline.acquire(0).await.unwrap();
+/// assert!(pipeline.try_acquire(1).is_err());
+///
+/// drop(parsed);
+/// let processed = pipeline.acquire(1).await.unwrap();
+/// drop(processed);
+/// let written = pipeline.acquire(2).await.unwrap();
+/// drop(written);
+///
+/// assert_eq!(pipeline.stage(0).available_permits(), 2);
+/// # });
+/// ```
+pub struct SemaphoreGroup {
+    stages: Vec<Semaphore>,
+    feeds: Vec<Option<usize>>,
+}
+
+impl SemaphoreGroup {
+    /// Creates a group with a stage for each entry of `permits`, holding
+    /// that many permits, where each stage feeds the next one.
+    pub fn new(permits: &[Permits]) -> SemaphoreGroup {
+        SemaphoreGroup::from_semaphores(permits.iter().map(|&n| Semaphore::new(n)).collect())
+    }
+
+    /// Creates a group from existing semaphores, where each one feeds the
+    /// next one.
+    pub fn from_semaphores(stages: Vec<Semaphore>) -> SemaphoreGroup {
+        let feeds = (1..=stages.len())
+            .map(|next| Some(next).filter(|&next| next < stages.len()))
+            .collect();
+        SemaphoreGroup { stages, feeds }
+    }
+
+    /// Makes the permits of stage `from` move to stage `to` when dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either stage does not exist.
+    pub fn feed(mut self, from: usize, to: usize) -> SemaphoreGroup {
+        assert!(to < self.stages.len(), "no pipeline stage {to}");
+        self.feeds[from] = Some(to);
+        self
+    }
+
+    /// Makes the permits of stage `stage` return to it when dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stage does not exist.
+    pub fn unfeed(mut self, stage: usize) -> SemaphoreGroup {
+        self.feeds[stage] = None;
+        self
+    }
+
+    /// Returns the semaphore of `stage`.
+    ///
+    /// Permits taken from it directly return to it as usual.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stage does not exist.
+    pub fn stage(&self, stage: usize) -> &Semaphore {
+        &self.stages[stage]
+    }
+
+    /// Returns the number of stages.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Returns whether the group has no stages.
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Waits for a permit of `stage`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stage does not exist.
+    pub async fn acquire(&self, stage: usize) -> Result<StagePermit<'_>, AcquireError> {
+        let permit = self.stages[stage].acquire().await?;
+        Ok(self.permit(stage, permit))
+    }
+
+    /// Takes a permit of `stage` if one is available right away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stage does not exist.
+    pub fn try_acquire(&self, stage: usize) -> Result<StagePermit<'_>, TryAcquireError> {
+        let permit = self.stages[stage].try_acquire_upto(1)?;
+        Ok(self.permit(stage, permit))
+    }
+
+    fn permit<'a>(&'a self, stage: usize, permit: SemaphorePermit<'a>) -> StagePermit<'a> {
+        StagePermit {
+            group: self,
+            stage,
+            permit: Some(permit),
+        }
+    }
+}
+
+impl fmt::Debug for SemaphoreGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphoreGroup")
+            .field("stages", &self.stages)
+            .field("feeds", &self.feeds)
+            .finish()
+    }
+}
+
+/// A permit of one stage of a [`SemaphoreGroup`].
+///
+/// Dropping the guard moves the permit to the stage this one feeds, or
+/// returns it if the stage feeds no other.
+#[must_use = "the permit is released as soon as the guard is dropped"]
+pub struct StagePermit<'a> {
+    group: &'a SemaphoreGroup,
+    stage: usize,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl StagePermit<'_> {
+    /// Returns the stage the permit was taken from.
+    pub fn stage(&self) -> usize {
+        self.stage
+    }
+}
+
+impl Drop for StagePermit<'_> {
+    fn drop(&mut self) {
+        let (Some(permit), Some(next)) = (self.permit.take(), self.group.feeds[self.stage]) else {
+            return;
+        };
+        let n = permit.num_permits();
+        permit.forget();
+        self.group.stages[next].add_permits(n);
+    }
+}
+
+impl fmt::Debug for StagePermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StagePermit")
+            .field("stage", &self.stage)
+            .field("feeds", &self.group.feeds[self.stage])
+            .finish()
+    }
+}
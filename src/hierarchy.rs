@@ -0,0 +1,185 @@
+use crate::multi::{acquire_each, try_acquire_each};
+use crate::{AcquireError, Permits, Semaphore, SemaphoreInner, TryAcquireError};
+use std::fmt;
+use std::sync::Arc;
+
+/// A semaphore linked to a parent, so an acquire also counts against the
+/// limits above it.
+///
+/// Each level of the hierarchy has its own permits, such as one level per
+/// customer under a global cap on requests in flight. An acquire takes
+/// permits from its own level and from every level up to the root, all at
+/// once: it waits while any level is short, and holds nothing in the
+/// meantime, so a task waiting on the global cap does not tie up its
+/// customer's permits. Dropping the guard returns the permits to every level.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::HierarchicalSemaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let global = HierarchicalSemaphore::root(3);
+/// let alice = global.child(2);
+/// let bob = global.child(2);
+///
+/// let a1 = alice.acquire().await.unwrap();
+/// let a2 = alice.acquire().await.unwrap();
+/// assert!(alice.try_acquire().is_err());
+///
+/// // Bob is limited by the global cap, with one permit left.
+/// let b1 = bob.acquire().await.unwrap();
+/// assert!(bob.try_acquire().is_err());
+/// assert_eq!(bob.available_permits(), 0);
+///
+/// drop(a1);
+/// assert!(bob.try_acquire().is_ok());
+/// # drop((a2, b1));
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct HierarchicalSemaphore {
+    level: Arc<Level>,
+}
+
+struct Level {
+    sema: Semaphore,
+    parent: Option<Arc<Level>>,
+}
+
+impl Level {
+    /// Returns this level and the ones above it, up to the root.
+    fn chain(&self) -> impl Iterator<Item = &Level> {
+        std::iter::successors(Some(self), |level| level.parent.as_deref())
+    }
+
+    /// Pairs each level of the chain with a request for `n` permits.
+    fn requests(&self, n: Permits) -> Vec<(&SemaphoreInner, Permits)> {
+        self.chain().map(|level| (&*level.sema.inner, n)).collect()
+    }
+}
+
+impl HierarchicalSemaphore {
+    /// Creates the root of a hierarchy with `permits` permits.
+    pub fn root(permits: Permits) -> HierarchicalSemaphore {
+        HierarchicalSemaphore::with_semaphore(Semaphore::new(permits))
+    }
+
+    /// Creates the root of a hierarchy holding the permits of `sema`.
+    pub fn with_semaphore(sema: Semaphore) -> HierarchicalSemaphore {
+        HierarchicalSemaphore {
+            level: Arc::new(Level { sema, parent: None }),
+        }
+    }
+
+    /// Creates a level below this one with `permits` permits of its own.
+    pub fn child(&self, permits: Permits) -> HierarchicalSemaphore {
+        self.child_with_semaphore(Semaphore::new(permits))
+    }
+
+    /// Creates a level below this one holding the permits of `sema`.
+    pub fn child_with_semaphore(&self, sema: Semaphore) -> HierarchicalSemaphore {
+        HierarchicalSemaphore {
+            level: Arc::new(Level {
+                sema,
+                parent: Some(self.level.clone()),
+            }),
+        }
+    }
+
+    /// Returns the level above this one, unless this is the root.
+    pub fn parent(&self) -> Option<HierarchicalSemaphore> {
+        let level = self.level.parent.clone()?;
+        Some(HierarchicalSemaphore { level })
+    }
+
+    /// Returns the semaphore holding the permits of this level alone.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.level.sema
+    }
+
+    /// Waits for a permit from this level and every level above it.
+    pub async fn acquire(&self) -> Result<HierarchicalPermit, AcquireError> {
+        self.acquire_many(1).await
+    }
+
+    /// Waits for `n` permits from this level and every level above it.
+    ///
+    /// The permits are taken from all levels at once, as soon as every level
+    /// has `n` available.
+    pub async fn acquire_many(&self, n: Permits) -> Result<HierarchicalPermit, AcquireError> {
+        acquire_each(&self.level.requests(n)).await?;
+        Ok(self.permit(n))
+    }
+
+    /// Takes a permit from this level and every level above it, if all have
+    /// one available right away.
+    pub fn try_acquire(&self) -> Result<HierarchicalPermit, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Takes `n` permits from this level and every level above it, if all
+    /// have them available right away.
+    pub fn try_acquire_many(&self, n: Permits) -> Result<HierarchicalPermit, TryAcquireError> {
+        try_acquire_each(&self.level.requests(n), None).map_err(|(_, err)| err)?;
+        Ok(self.permit(n))
+    }
+
+    /// Returns the number of permits an acquire could take right now, the
+    /// fewest available at any level of the chain.
+    pub fn available_permits(&self) -> Permits {
+        self.level
+            .chain()
+            .map(|level| level.sema.available_permits())
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn permit(&self, permits: Permits) -> HierarchicalPermit {
+        HierarchicalPermit {
+            level: self.level.clone(),
+            permits,
+        }
+    }
+}
+
+impl fmt::Debug for HierarchicalSemaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HierarchicalSemaphore")
+            .field("available", &self.level.sema.available_permits())
+            .field("depth", &(self.level.chain().count() - 1))
+            .finish()
+    }
+}
+
+/// Permits taken from every level of a [`HierarchicalSemaphore`] chain.
+///
+/// Dropping the guard returns the permits to each level.
+#[must_use = "the permits are released as soon as the guard is dropped"]
+pub struct HierarchicalPermit {
+    level: Arc<Level>,
+    permits: Permits,
+}
+
+impl HierarchicalPermit {
+    /// Returns the number of permits held at each level.
+    pub fn num_permits(&self) -> Permits {
+        self.permits
+    }
+}
+
+impl Drop for HierarchicalPermit {
+    fn drop(&mut self) {
+        for level in self.level.chain() {
+            level.sema.inner.release(self.permits);
+        }
+    }
+}
+
+impl fmt::Debug for HierarchicalPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HierarchicalPermit")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
@@ -0,0 +1,274 @@
+use crate::deadlock::Hold;
+use crate::time;
+use crate::{
+    AcquireError, OwnedSemaphorePermit, Permits, Semaphore, SemaphoreBuilder, TryAcquireError,
+};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A map of semaphores, one for each key.
+///
+/// Limits operations per key, such as at most four concurrent requests for
+/// each tenant. The semaphore of a key is created the first time the key is
+/// used, from a builder shared by all keys, so every key gets the same
+/// configuration.
+///
+/// A key is idle while no permit, pending acquire or handle to its
+/// semaphore is left outside the map. Idle keys can be evicted to keep the
+/// map small under churn, after an [`idle_timeout`] or once the map holds
+/// [`max_keys`] keys. An evicted key gets a new semaphore when it is used
+/// again. Keys in use are never evicted, so the limit per key always holds.
+///
+/// [`idle_timeout`]: KeyedSemaphore::idle_timeout
+/// [`max_keys`]: KeyedSemaphore::max_keys
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::KeyedSemaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let limits = KeyedSemaphore::<String>::new(2);
+///
+/// let a1 = limits.acquire("alice").await.unwrap();
+/// let a2 = limits.acquire("alice").await.unwrap();
+/// assert!(limits.try_acquire("alice").is_err());
+///
+/// // Other tenants have permits of their own.
+/// let b = limits.acquire("bob").await.unwrap();
+/// assert_eq!(limits.len(), 2);
+///
+/// drop(a1);
+/// assert!(limits.try_acquire("alice").is_ok());
+/// # drop((a2, b));
+/// # });
+/// ```
+pub struct KeyedSemaphore<K> {
+    builder: SemaphoreBuilder,
+    idle_timeout: Option<Duration>,
+    max_keys: Option<usize>,
+    map: Mutex<Map<K>>,
+}
+
+struct Map<K> {
+    entries: HashMap<K, Entry>,
+    /// When idle keys were last checked for the timeout.
+    swept: Instant,
+}
+
+struct Entry {
+    sema: Semaphore,
+    used: Instant,
+}
+
+impl Entry {
+    fn is_idle(&self) -> bool {
+        // Permits, acquires and leases hold the state, and clones the handle.
+        Arc::strong_count(&self.sema.inner) == 1 && Arc::strong_count(&self.sema.handle) == 1
+    }
+}
+
+impl<K: Eq + Hash> Map<K> {
+    /// Removes the keys idle for `timeout`.
+    fn sweep(&mut self, now: Instant, timeout: Duration) {
+        self.entries
+            .retain(|_, e| !e.is_idle() || now.saturating_duration_since(e.used) < timeout);
+        self.swept = now;
+    }
+
+    /// Removes the least recently used idle keys until at most `max` are left.
+    fn shrink(&mut self, max: usize) {
+        let excess = self.entries.len().saturating_sub(max);
+        if excess == 0 {
+            return;
+        }
+        let mut idle: Vec<Instant> = self
+            .entries
+            .values()
+            .filter(|e| e.is_idle())
+            .map(|e| e.used)
+            .collect();
+        if idle.is_empty() {
+            return;
+        }
+        let nth = excess.min(idle.len()) - 1;
+        let (_, &mut cutoff, _) = idle.select_nth_unstable(nth);
+        let mut left = excess;
+        self.entries.retain(|_, e| {
+            let evict = left > 0 && e.used <= cutoff && e.is_idle();
+            left -= evict as usize;
+            !evict
+        });
+    }
+}
+
+impl<K: Eq + Hash> KeyedSemaphore<K> {
+    /// Creates a map giving each key a semaphore with `permits` permits.
+    pub fn new(permits: Permits) -> KeyedSemaphore<K> {
+        KeyedSemaphore::with_builder(Semaphore::builder().permits(permits))
+    }
+
+    /// Creates a map building the semaphore of each key with `builder`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{KeyedSemaphore, Semaphore};
+    ///
+    /// let limits = KeyedSemaphore::<u32>::with_builder(Semaphore::builder().permits(4).fair(true));
+    /// assert_eq!(limits.semaphore(&7).available_permits(), 4);
+    /// ```
+    pub fn with_builder(builder: SemaphoreBuilder) -> KeyedSemaphore<K> {
+        KeyedSemaphore {
+            builder,
+            idle_timeout: None,
+            max_keys: None,
+            map: Mutex::new(Map {
+                entries: HashMap::new(),
+                swept: time::now(),
+            }),
+        }
+    }
+
+    /// Evicts keys once they have been idle for `timeout`.
+    ///
+    /// Idle keys are looked for as new keys are added, at most once every
+    /// `timeout`, so a key may stay up to twice as long. Call
+    /// [`evict_idle`] to look for them right away.
+    ///
+    /// [`evict_idle`]: KeyedSemaphore::evict_idle
+    pub fn idle_timeout(mut self, timeout: Duration) -> KeyedSemaphore<K> {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Bounds the number of keys, evicting the least recently used idle
+    /// keys as new ones are added.
+    ///
+    /// While more than `max` keys are in use at once the map holds them all,
+    /// and shrinks back as they turn idle and new keys come in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::KeyedSemaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let limits = KeyedSemaphore::<u32>::new(1).max_keys(2);
+    ///
+    /// let held = limits.acquire(&1).await.unwrap();
+    /// drop(limits.acquire(&2).await.unwrap());
+    /// drop(limits.acquire(&3).await.unwrap());
+    ///
+    /// // Key 2 was idle and least recently used, key 1 is still held.
+    /// assert_eq!(limits.len(), 2);
+    /// assert!(limits.contains_key(&1));
+    /// assert!(!limits.contains_key(&2));
+    /// # drop(held);
+    /// # });
+    /// ```
+    pub fn max_keys(mut self, max: usize) -> KeyedSemaphore<K> {
+        self.max_keys = Some(max);
+        self
+    }
+
+    /// Returns the semaphore of `key`, creating it if needed.
+    pub fn semaphore<Q>(&self, key: &Q) -> Semaphore
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let mut map = self.map.lock().unwrap();
+        let now = time::now();
+        if let Some(entry) = map.entries.get_mut(key) {
+            entry.used = now;
+            return entry.sema.clone();
+        }
+
+        if let Some(timeout) = self.idle_timeout {
+            if now.saturating_duration_since(map.swept) >= timeout {
+                map.sweep(now, timeout);
+            }
+        }
+        if let Some(max) = self.max_keys {
+            map.shrink(max.saturating_sub(1));
+        }
+        let sema = self.builder.clone().build();
+        map.entries.insert(
+            key.to_owned(),
+            Entry {
+                sema: sema.clone(),
+                used: now,
+            },
+        );
+        sema
+    }
+
+    /// Waits for a permit of `key`.
+    ///
+    /// The guard holds its own handle to the semaphore of the key, so it
+    /// does not borrow the map.
+    pub async fn acquire<Q>(&self, key: &Q) -> Result<OwnedSemaphorePermit, AcquireError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        self.semaphore(key).acquire_owned().await
+    }
+
+    /// Takes a permit of `key` if one is available right away.
+    pub fn try_acquire<Q>(&self, key: &Q) -> Result<OwnedSemaphorePermit, TryAcquireError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        let sema = self.semaphore(key);
+        sema.inner.try_acquire_exact(1)?;
+        Ok(OwnedSemaphorePermit {
+            inner: sema.inner.clone(),
+            permits: 1,
+            hold: Hold::none(),
+        })
+    }
+
+    /// Evicts the keys idle for the idle timeout, or all idle keys if no
+    /// timeout is set.
+    pub fn evict_idle(&self) {
+        let timeout = self.idle_timeout.unwrap_or(Duration::ZERO);
+        self.map.lock().unwrap().sweep(time::now(), timeout);
+    }
+
+    /// Returns whether `key` has a semaphore.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.lock().unwrap().entries.contains_key(key)
+    }
+
+    /// Returns the number of keys with a semaphore.
+    pub fn len(&self) -> usize {
+        self.map.lock().unwrap().entries.len()
+    }
+
+    /// Returns whether no key has a semaphore yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K> fmt::Debug for KeyedSemaphore<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyedSemaphore")
+            .field("keys", &self.map.lock().unwrap().entries.len())
+            .field("builder", &self.builder)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("max_keys", &self.max_keys)
+            .finish()
+    }
+}
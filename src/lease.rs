@@ -0,0 +1,143 @@
+use crate::time::{self, Alarm};
+use crate::{LeaseExpired, Permits, SemaphoreInner};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Wake, Waker};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct LeaseInner {
+    sema: Arc<SemaphoreInner>,
+    permits: Permits,
+    ttl: Duration,
+    expired: AtomicBool,
+    deadline: Mutex<Instant>,
+    alarm: Mutex<Option<Alarm>>,
+}
+
+impl LeaseInner {
+    /// Returns the permits to the semaphore unless that already happened.
+    fn finish(&self) {
+        if !self.expired.swap(true, Ordering::AcqRel) {
+            self.sema.release(self.permits);
+        }
+    }
+
+    /// Moves the deadline to one time to live from now.
+    fn schedule(self: &Arc<Self>, deadline: &mut Instant) {
+        let mut alarm = self.alarm.lock().unwrap();
+        match time::now().checked_add(self.ttl) {
+            Some(next) => {
+                *deadline = next;
+                *alarm = Some(Alarm::new(next, Waker::from(self.clone())));
+            }
+            None => *alarm = None,
+        }
+    }
+}
+
+/// The timer wakes the lease once its deadline has passed.
+impl Wake for LeaseInner {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let deadline = self.deadline.lock().unwrap();
+        // A renewal may have raced with the timer firing the previous alarm.
+        if time::now() >= *deadline {
+            self.finish();
+            drop(deadline);
+            self.alarm.lock().unwrap().take();
+        }
+    }
+}
+
+/// Permits acquired by [`Semaphore::acquire_lease`] that expire after a time
+/// to live.
+///
+/// Unless [`renew`] is called in time, the permits are returned to the
+/// semaphore once the lease expires, even if the holder is stuck. The holder
+/// can detect this with [`is_expired`]. Dropping the lease releases the
+/// permits early.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+/// use std::time::Duration;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(1);
+///
+/// let lease = s.acquire_lease(1, Duration::from_millis(20)).await.unwrap();
+/// assert_eq!(s.available_permits(), 0);
+///
+/// s.acquire().await.unwrap();
+/// assert!(lease.is_expired());
+/// assert!(lease.renew().is_err());
+/// # });
+/// ```
+///
+/// [`Semaphore::acquire_lease`]: crate::Semaphore::acquire_lease
+/// [`renew`]: Lease::renew
+/// [`is_expired`]: Lease::is_expired
+#[must_use = "the permits are released as soon as the lease is dropped"]
+#[derive(Debug)]
+pub struct Lease {
+    inner: Arc<LeaseInner>,
+}
+
+impl Lease {
+    pub(crate) fn new(sema: Arc<SemaphoreInner>, permits: Permits, ttl: Duration) -> Self {
+        let inner = Arc::new(LeaseInner {
+            sema,
+            permits,
+            ttl,
+            expired: AtomicBool::new(false),
+            deadline: Mutex::new(time::now()),
+            alarm: Mutex::new(None),
+        });
+        inner.schedule(&mut inner.deadline.lock().unwrap());
+        Lease { inner }
+    }
+
+    /// Returns the number of permits held by this lease.
+    pub fn num_permits(&self) -> Permits {
+        self.inner.permits
+    }
+
+    /// Returns the time to live the lease is renewed for.
+    pub fn ttl(&self) -> Duration {
+        self.inner.ttl
+    }
+
+    /// Returns whether the lease expired and its permits were returned.
+    pub fn is_expired(&self) -> bool {
+        self.inner.expired.load(Ordering::Acquire)
+    }
+
+    /// Extends the lease by its time to live, counted from now.
+    ///
+    /// Returns an error if the lease has already expired, in which case the
+    /// holder no longer owns any permits.
+    pub fn renew(&self) -> Result<(), LeaseExpired> {
+        let mut deadline = self.inner.deadline.lock().unwrap();
+        if self.is_expired() {
+            return Err(LeaseExpired);
+        }
+        self.inner.schedule(&mut deadline);
+        Ok(())
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if !self.is_expired() {
+            self.inner.sema.poison_if_panicking();
+        }
+        self.inner.finish();
+        // Dropping the alarm also frees the waker that refers back to the lease.
+        self.inner.alarm.lock().unwrap().take();
+    }
+}
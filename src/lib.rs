@@ -1,76 +1,729 @@
-use event_listener::Event;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+mod acquire;
+mod binary;
+mod blocking;
+mod budget;
+mod builder;
+mod cancel;
+pub mod channel;
+mod coop;
+mod deadlock;
+mod error;
+mod ext;
+mod grant_log;
+mod group;
+mod hierarchy;
+mod keyed;
+mod lease;
+pub mod limit;
+mod multi;
+mod owner;
+mod permit_stream;
+mod pipeline;
+pub mod pool;
+mod primitive;
+mod queue;
+mod releases;
+mod reservation;
+#[cfg(feature = "sim")]
+pub mod sim;
+pub mod sink;
+#[cfg(feature = "tokio")]
+mod spawn;
+mod state;
+mod static_semaphore;
+pub mod stream;
+pub mod sync;
+mod task_group;
+mod time;
+mod unordered;
+mod watch;
+
+pub use acquire::Acquire;
+pub use binary::{BinarySemaphore, BinarySemaphoreGuard};
+pub use budget::{BudgetPermit, BudgetSemaphore};
+pub use builder::{SemaphoreBuilder, WakeOrder};
+pub use cancel::CancellationToken;
+#[cfg(feature = "deadlock-detection")]
+pub use deadlock::{deadlocks, Deadlock};
+pub use error::{AcquireError, LeaseExpired, TryAcquireError};
+pub use ext::{FutureExt, WithPermit};
+#[cfg(feature = "grant-log")]
+pub use grant_log::Grant;
+pub use group::{SemaphoreGroup, StagePermit};
+pub use hierarchy::{HierarchicalPermit, HierarchicalSemaphore};
+pub use keyed::KeyedSemaphore;
+pub use lease::Lease;
+pub use multi::{acquire_all, try_acquire_all, MultiPermit};
+pub use owner::{OwnerPermit, OwnerSemaphore};
+pub use permit_stream::PermitStream;
+pub use pipeline::{Pipeline, PipelineStage};
+pub use releases::Releases;
+pub use reservation::Reservation;
+pub use sink::SinkExt;
+#[cfg(feature = "tokio")]
+pub use spawn::LimitedJoinSet;
+pub use state::SemaphoreState;
+pub use static_semaphore::StaticSemaphore;
+pub use stream::StreamExt;
+pub use task_group::TaskGroup;
+pub use watch::Watch;
+
+/// The integer type used to count permits.
+///
+/// This is `usize` by default. Enabling the `u64` feature makes it `u64`, so
+/// 32-bit targets get the same permit range as 64-bit ones.
+#[cfg(not(feature = "u64"))]
+pub type Permits = usize;
+
+/// The integer type used to count permits.
+///
+/// This is `usize` by default. Enabling the `u64` feature makes it `u64`, so
+/// 32-bit targets get the same permit range as 64-bit ones.
+#[cfg(feature = "u64")]
+pub type Permits = u64;
+
+use acquire::RawAcquire;
+
+use deadlock::Hold;
+use grant_log::GrantLog;
+use primitive::{fence, AtomicBool, AtomicPermits, AtomicUsize, Mutex, Ordering};
+use queue::{Node, NodeState, Poller, WaitQueue};
+use releases::Subscribers;
+use std::future::{poll_fn, Future};
+use std::sync::{Arc, Weak};
+use std::task::{ready, Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// How long the head of the queue may be overtaken before it blocks newcomers.
+const STARVATION_LIMIT: Duration = Duration::from_millis(1);
+
+/// Behavior selected when the semaphore is built.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Config {
+    pub max: Permits,
+    pub fair: bool,
+    pub order: WakeOrder,
+    pub timeout: Option<Duration>,
+    pub poison: bool,
+}
+
+impl Config {
+    pub const fn new() -> Config {
+        Config {
+            max: Permits::MAX,
+            fair: false,
+            order: WakeOrder::Fifo,
+            timeout: None,
+            poison: false,
+        }
+    }
+}
+
+/// Result of trying to acquire before parking.
+pub(crate) enum Enqueued {
+    Acquired(Permits),
+    Queued(Arc<Node>),
+}
 
 #[derive(Debug)]
 pub(crate) struct SemaphoreInner {
-    count: AtomicUsize,
-    event: Event,
+    count: AtomicPermits,
+    capacity: AtomicPermits,
+    debt: AtomicPermits,
+    waiters: AtomicUsize,
+    starving: AtomicBool,
+    closed: AtomicBool,
+    poisoned: AtomicBool,
+    config: Config,
+    queue: Mutex<WaitQueue>,
+    pollers: Mutex<Vec<Poller>>,
+    /// Whether any poller is registered, so taking permits only looks at
+    /// the pollers when someone watches.
+    polled: AtomicBool,
+    releases: Subscribers,
+    grants: GrantLog,
 }
 
 impl SemaphoreInner {
-    pub const fn new(n: usize) -> Self {
+    pub fn new(n: Permits) -> Self {
+        Self::with_config(n, Config::new())
+    }
+
+    pub fn with_config(n: Permits, config: Config) -> Self {
         Self {
-            count: AtomicUsize::new(n),
-            event: Event::new(),
+            count: AtomicPermits::new(n),
+            capacity: AtomicPermits::new(n),
+            debt: AtomicPermits::new(0),
+            waiters: AtomicUsize::new(0),
+            starving: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            poisoned: AtomicBool::new(false),
+            config,
+            queue: Mutex::new(WaitQueue::new()),
+            pollers: Mutex::new(Vec::new()),
+            polled: AtomicBool::new(false),
+            releases: Subscribers::new(),
+            grants: GrantLog::new(),
         }
     }
 
-    pub fn try_acquire(&self, count: usize) -> usize {
+    pub fn try_acquire(&self, count: Permits) -> Permits {
+        self.try_acquire_range(0, count).unwrap_or(0)
+    }
+
+    /// Takes at least `min` and up to `max` permits, or nothing.
+    pub fn try_acquire_range(&self, min: Permits, max: Permits) -> Option<Permits> {
         let mut balance = self.count.load(Ordering::Acquire);
         loop {
-            if balance == 0 {
-                return 0;
+            if balance < min {
+                return None;
             }
-            let dest = match balance >= count {
-                true => balance - count,
-                false => 0,
-            };
+            let taken = balance.min(max);
 
             match self.count.compare_exchange_weak(
                 balance,
-                dest,
+                balance - taken,
                 Ordering::AcqRel,
                 Ordering::Acquire,
             ) {
-                Ok(_) => return balance - dest,
+                Ok(_) => {
+                    if taken > 0 {
+                        self.notify_taken();
+                    }
+                    return Some(taken);
+                }
                 Err(c) => balance = c,
             }
         }
     }
 
-    pub async fn acquire(&self, count: usize) {
-        let mut listener = None;
-        let mut acquired = 0;
+    pub fn try_acquire_exact(&self, count: Permits) -> Result<(), TryAcquireError> {
+        if self.is_closed() {
+            return Err(TryAcquireError::Closed);
+        }
+        if self.is_poisoned() {
+            return Err(TryAcquireError::Poisoned);
+        }
+        if !self.may_take() {
+            return Err(TryAcquireError::NoPermits);
+        }
+
+        match self.try_acquire_range(count, count) {
+            Some(_) => Ok(()),
+            None => Err(TryAcquireError::NoPermits),
+        }
+    }
+
+    pub fn acquire(&self, count: Permits) -> RawAcquire<'_> {
+        RawAcquire::new(self, count)
+    }
+
+    /// Acquires between `min` and `max` permits, or queues a waiter woken by `waker`.
+    pub fn acquire_or_enqueue(
+        &self,
+        min: Permits,
+        max: Permits,
+        priority: u8,
+        waker: &Waker,
+    ) -> Result<Enqueued, AcquireError> {
+        if self.is_closed() {
+            return Err(AcquireError::Closed);
+        }
+        if self.is_poisoned() {
+            return Err(AcquireError::Poisoned);
+        }
+        let waiter = self.grants.waiter();
+        // In fair mode newcomers may not overtake tasks that are served before
+        // them, and neither may they while the head of the queue is starving.
+        if self.may_take() {
+            if let Some(n) = self.try_acquire_range(min, max) {
+                self.grants.record(waiter, n);
+                return Ok(Enqueued::Acquired(n));
+            }
+        }
+
+        let mut queue = self.queue.lock().unwrap();
+        if self.is_closed() {
+            return Err(AcquireError::Closed);
+        }
+
+        // Announce the waiter before checking again, so that a concurrent
+        // `release` either sees it or leaves its permits for this check.
+        self.waiters.fetch_add(1, Ordering::AcqRel);
+        fence(Ordering::SeqCst);
+        let pos = queue.position(priority, self.config.order);
+        if self.may_barge() || (self.config.fair && pos == 0) {
+            if let Some(n) = self.try_acquire_range(min, max) {
+                self.waiters.fetch_sub(1, Ordering::AcqRel);
+                self.grants.record(waiter, n);
+                return Ok(Enqueued::Acquired(n));
+            }
+        }
+
+        let node = Node::new(min, max, priority, waiter, waker);
+        queue.insert(pos, node.clone());
+        Ok(Enqueued::Queued(node))
+    }
+
+    /// Removes a waiter whose acquire future was dropped.
+    pub fn cancel(&self, node: &Arc<Node>) {
+        let mut wakers = Vec::new();
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.remove(node) {
+                self.waiters.fetch_sub(1, Ordering::AcqRel);
+                // A blocked head may have been holding back the waiters behind it.
+                if self.config.fair || self.starving.load(Ordering::Acquire) {
+                    self.dispatch(&mut queue, &mut wakers);
+                }
+            }
+        }
+        wakers.into_iter().for_each(Waker::wake);
+
+        if let NodeState::Granted(n) = *node.state.lock().unwrap() {
+            self.release(n);
+        }
+    }
+
+    /// Whether a newcomer may take permits ahead of queued waiters.
+    fn may_barge(&self) -> bool {
+        !self.config.fair && !self.starving.load(Ordering::Acquire)
+    }
+
+    /// Whether a newcomer may take available permits without queueing.
+    pub fn may_take(&self) -> bool {
+        self.may_barge() || self.waiters.load(Ordering::Acquire) == 0
+    }
+
+    /// Hands available permits to queued waiters.
+    ///
+    /// In fair mode waiters are served strictly in order, otherwise a waiter
+    /// that cannot be satisfied is skipped. A head that has been skipped for
+    /// longer than `STARVATION_LIMIT` blocks the queue until it is served, so
+    /// large requests are not starved by a stream of small ones.
+    fn dispatch(&self, queue: &mut WaitQueue, wakers: &mut Vec<Waker>) {
+        let mut blocked = false;
+        let mut head = true;
+        let mut starving = false;
+        queue.retain(|node| {
+            if blocked {
+                return true;
+            }
+            let first = std::mem::replace(&mut head, false);
+            match self.try_acquire_range(node.min, node.max) {
+                Some(n) => {
+                    self.waiters.fetch_sub(1, Ordering::AcqRel);
+                    self.grants.record(node.waiter, n);
+                    wakers.extend(node.complete(NodeState::Granted(n)));
+                    false
+                }
+                None => {
+                    starving = !self.config.fair
+                        && first
+                        && time::now().saturating_duration_since(node.queued_at)
+                            >= STARVATION_LIMIT;
+                    blocked =
+                        self.config.fair || starving || self.count.load(Ordering::Acquire) == 0;
+                    true
+                }
+            }
+        });
+        self.starving.store(starving, Ordering::Release);
+    }
 
+    /// Takes up to `n` released permits to pay off a pending shrink.
+    fn absorb(&self, n: Permits) -> Permits {
+        let mut debt = self.debt.load(Ordering::Acquire);
         loop {
-            acquired += self.try_acquire(count - acquired);
-            if count == acquired {
-                return;
+            if debt == 0 {
+                return 0;
             }
+            let taken = debt.min(n);
 
-            match listener.take() {
-                None => listener = Some(self.event.listen()),
-                Some(l) => l.await,
+            match self.debt.compare_exchange_weak(
+                debt,
+                debt - taken,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return taken,
+                Err(d) => debt = d,
             }
         }
     }
 
-    pub fn add_permits(&self, n: usize) {
+    pub fn release(&self, n: Permits) {
+        if n > 0 {
+            self.releases.record(n);
+        }
+        let n = n - self.absorb(n);
+        if n == 0 {
+            return;
+        }
+
         self.count.fetch_add(n, Ordering::AcqRel);
-        self.event.notify(n);
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::Acquire) > 0 {
+            let mut wakers = Vec::new();
+            self.dispatch(&mut self.queue.lock().unwrap(), &mut wakers);
+            wakers.into_iter().for_each(Waker::wake);
+        }
+        self.wake_pollers(false);
+    }
+
+    pub fn poll_acquire(
+        &self,
+        cx: &mut Context<'_>,
+        count: Permits,
+    ) -> Poll<Result<(), AcquireError>> {
+        self.poll_changed(cx, count, || match self.try_acquire_exact(count) {
+            Ok(()) => Poll::Ready(Ok(())),
+            Err(TryAcquireError::Closed) => Poll::Ready(Err(AcquireError::Closed)),
+            Err(TryAcquireError::Poisoned) => Poll::Ready(Err(AcquireError::Poisoned)),
+            Err(TryAcquireError::NoPermits) => Poll::Pending,
+        })
+    }
+
+    pub fn poll_idle(&self, cx: &mut Context<'_>) -> Poll<()> {
+        self.poll_changed(cx, 0, || {
+            if self.is_idle() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+
+    pub fn poll_available(&self, cx: &mut Context<'_>, n: Permits) -> Poll<()> {
+        self.poll_changed(cx, 0, || {
+            if self.available_permits() >= n {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+
+    /// No permits are held once the balance matches the capacity and no shrink is pending.
+    fn is_idle(&self) -> bool {
+        self.debt.load(Ordering::Acquire) == 0
+            && self.count.load(Ordering::Acquire) == self.capacity.load(Ordering::Acquire)
+    }
+
+    /// Polls `f`, retrying it whenever the permit balance changes until it is ready.
+    ///
+    /// A non-zero `demand` is the number of permits `f` needs to make progress,
+    /// so the task is only woken once that many are released.
+    pub fn poll_changed<T>(
+        &self,
+        cx: &mut Context<'_>,
+        demand: Permits,
+        mut f: impl FnMut() -> Poll<T>,
+    ) -> Poll<T> {
+        let mut registered = false;
+        loop {
+            match f() {
+                Poll::Ready(output) => return Poll::Ready(output),
+                Poll::Pending if registered => return Poll::Pending,
+                Poll::Pending => {}
+            }
+
+            // Register before checking again so a concurrent release is not missed.
+            let mut pollers = self.pollers.lock().unwrap();
+            match pollers.iter_mut().find(|p| p.waker.will_wake(cx.waker())) {
+                Some(poller) => poller.demand = demand,
+                None => pollers.push(Poller {
+                    waker: cx.waker().clone(),
+                    demand,
+                }),
+            }
+            self.polled.store(true, Ordering::SeqCst);
+            drop(pollers);
+            // Pairs with the fence in `notify_taken`.
+            fence(Ordering::SeqCst);
+            registered = true;
+        }
+    }
+
+    /// Wakes the pollers watching the balance after permits were taken.
+    fn notify_taken(&self) {
+        // Either this sees the poller registered, or the poller sees the
+        // balance after the permits were taken.
+        fence(Ordering::SeqCst);
+        if self.polled.load(Ordering::SeqCst) {
+            self.wake_pollers(false);
+        }
+    }
+
+    /// Wakes the pollers that may now make progress.
+    ///
+    /// Pollers waiting for permits are woken in order while the available
+    /// permits cover their demand, so a release does not wake more tasks than
+    /// it can satisfy. With `all`, every poller is woken regardless.
+    fn wake_pollers(&self, all: bool) {
+        let mut budget = self.available_permits();
+        let mut wakers = Vec::new();
+        let mut pollers = self.pollers.lock().unwrap();
+        pollers.retain(|poller| {
+            if !all && poller.demand > 0 {
+                if poller.demand > budget {
+                    return true;
+                }
+                budget -= poller.demand;
+            }
+            wakers.push(poller.waker.clone());
+            false
+        });
+        self.polled.store(!pollers.is_empty(), Ordering::SeqCst);
+        drop(pollers);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    pub fn add_permits(&self, n: Permits) {
+        let mut capacity = self.capacity.load(Ordering::Acquire);
+        loop {
+            let grown = capacity.saturating_add(n);
+            assert!(
+                grown <= self.config.max,
+                "permits exceed the semaphore maximum of {}",
+                self.config.max
+            );
+
+            match self.capacity.compare_exchange_weak(
+                capacity,
+                grown,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return self.release(grown - capacity),
+                Err(c) => capacity = c,
+            }
+        }
+    }
+
+    pub fn capacity(&self) -> Permits {
+        self.capacity.load(Ordering::Acquire)
+    }
+
+    pub fn forget_permits(&self, n: Permits) {
+        let absorbed = self.absorb(n);
+        self.capacity.fetch_sub(n - absorbed, Ordering::AcqRel);
+        self.wake_pollers(false);
+    }
+
+    pub fn set_permits(&self, n: Permits) {
+        assert!(
+            n <= self.config.max,
+            "permits exceed the semaphore maximum of {}",
+            self.config.max
+        );
+        let old = self.capacity.swap(n, Ordering::AcqRel);
+        if n > old {
+            self.release(n - old);
+        } else {
+            let shrink = old - n;
+            let taken = self.try_acquire(shrink);
+            self.debt.fetch_add(shrink - taken, Ordering::AcqRel);
+            self.wake_pollers(false);
+        }
+    }
+
+    pub fn available_permits(&self) -> Permits {
+        self.count.load(Ordering::Acquire)
+    }
+
+    pub fn num_waiters(&self) -> usize {
+        self.waiters.load(Ordering::Acquire)
+    }
+
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+
+        let wakers: Vec<_> = {
+            let mut queue = self.queue.lock().unwrap();
+            self.waiters.store(0, Ordering::Release);
+            self.starving.store(false, Ordering::Release);
+            queue
+                .drain()
+                .filter_map(|node| node.complete(NodeState::Closed))
+                .collect()
+        };
+        wakers.into_iter().for_each(Waker::wake);
+        self.wake_pollers(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Marks the semaphore as poisoned if permits are released by a panicking thread.
+    pub fn poison_if_panicking(&self) {
+        if self.config.poison && std::thread::panicking() {
+            self.poisoned.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    #[cfg(feature = "grant-log")]
+    pub fn take_grant_log(&self) -> Vec<Grant> {
+        self.grants.take()
+    }
+
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Release);
+    }
+
+    pub fn state(&self) -> SemaphoreState {
+        let _queue = self.queue.lock().unwrap();
+        SemaphoreState {
+            available: self.available_permits(),
+            capacity: self.capacity(),
+            waiters: self.num_waiters(),
+            closed: self.is_closed(),
+        }
     }
 }
 
 /// A counter for limiting the number of concurrent operations.
+///
+/// `Semaphore` is `Send` and `Sync` without any unsafe code, as are its
+/// permits, the futures returned by its methods and the other types of this
+/// crate, so all of them can be shared or moved across threads. Only the
+/// locks in [`sync`] need unsafe code to hand out access to their data.
+///
+/// Dropping the last clone of a semaphore closes it, as no one is left to add
+/// permits. Permits that are still held can be released as usual, but weak
+/// handles no longer upgrade and anything still waiting fails with `Closed`.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(1);
+/// let weak = s.downgrade();
+///
+/// let guard = s.acquire_owned().await.unwrap();
+/// drop(s);
+/// assert!(weak.upgrade().is_none());
+/// drop(guard);
+/// # });
+/// ```
 #[derive(Debug, Clone)]
 pub struct Semaphore {
     inner: Arc<SemaphoreInner>,
+    handle: Arc<Handle>,
+}
+
+/// Shared by all clones of a [`Semaphore`], closing it once the last one is dropped.
+#[derive(Debug)]
+struct Handle {
+    inner: Weak<SemaphoreInner>,
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.close();
+        }
+    }
 }
 
-unsafe impl Send for Semaphore {}
+// Fails to compile if a public type loses its thread safety.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+
+    type BoxStream = std::pin::Pin<Box<dyn futures_core::Stream<Item = ()> + Send + Sync>>;
+    type Ready = std::future::Ready<()>;
+    type BoxFutures = std::pin::Pin<Box<dyn futures_core::Stream<Item = Ready> + Send + Sync>>;
+
+    assert_send_sync::<Semaphore>();
+    assert_send_sync::<WeakSemaphore>();
+    assert_send_sync::<StaticSemaphore>();
+    assert_send_sync::<BinarySemaphore>();
+    assert_send_sync::<SemaphorePermit<'_>>();
+    assert_send_sync::<OwnedSemaphorePermit>();
+    assert_send_sync::<BinarySemaphoreGuard<'_>>();
+    assert_send_sync::<Acquire<'_>>();
+    assert_send_sync::<WithPermit<'_, Ready>>();
+    assert_send_sync::<Reservation<'_>>();
+    assert_send_sync::<Lease>();
+    assert_send_sync::<KeyedSemaphore<String>>();
+    assert_send_sync::<BudgetSemaphore>();
+    assert_send_sync::<BudgetPermit<'_>>();
+    assert_send_sync::<HierarchicalSemaphore>();
+    assert_send_sync::<HierarchicalPermit>();
+    assert_send_sync::<MultiPermit<'_>>();
+    assert_send_sync::<SemaphoreGroup>();
+    assert_send_sync::<StagePermit<'_>>();
+    assert_send_sync::<OwnerSemaphore<u64>>();
+    assert_send_sync::<OwnerPermit<'_, u64>>();
+    assert_send_sync::<Watch>();
+    assert_send_sync::<Releases>();
+    assert_send_sync::<PermitStream>();
+    assert_send_sync::<TaskGroup>();
+    assert_send_sync::<Pipeline>();
+    assert_send_sync::<PipelineStage>();
+    #[cfg(feature = "tokio")]
+    assert_send_sync::<LimitedJoinSet<()>>();
+    assert_send_sync::<CancellationToken>();
+    assert_send_sync::<SemaphoreBuilder>();
+    assert_send_sync::<SemaphoreState>();
+    assert_send_sync::<AcquireError>();
+    assert_send_sync::<TryAcquireError>();
+    assert_send_sync::<channel::Sender<()>>();
+    assert_send_sync::<channel::Receiver<()>>();
+    assert_send_sync::<limit::AimdLimiter>();
+    assert_send_sync::<limit::Bulkhead>();
+    assert_send_sync::<limit::BreakerPermit<'_>>();
+    assert_send_sync::<limit::CircuitBreaker>();
+    assert_send_sync::<limit::GradientLimiter>();
+    assert_send_sync::<limit::LeakyBucket>();
+    assert_send_sync::<limit::Limiter>();
+    assert_send_sync::<limit::RateLimiter>();
+    assert_send_sync::<limit::SlidingWindow>();
+    assert_send_sync::<pool::Pool<()>>();
+    assert_send_sync::<pool::Pooled<'_, ()>>();
+    assert_send_sync::<sink::LimitUnflushed<'_, ()>>();
+    assert_send_sync::<sink::SinkError<()>>();
+    assert_send_sync::<stream::MapConcurrentLimited<'_, BoxStream, fn(()) -> Ready, Ready>>();
+    assert_send_sync::<stream::BufferedLimited<'_, BoxFutures>>();
+    assert_send_sync::<stream::ForEachConcurrentLimited<'_, BoxStream, fn(()) -> Ready, Ready>>();
+    assert_send_sync::<stream::ThrottleBy<'_, BoxStream>>();
+    assert_send_sync::<stream::LimitedFuturesUnordered<'_, Ready>>();
+    assert_send_sync::<sync::AsyncOnce<()>>();
+    assert_send_sync::<sync::Barrier>();
+    assert_send_sync::<sync::Condvar>();
+    assert_send_sync::<sync::CountdownLatch>();
+    assert_send_sync::<sync::Doorbell>();
+    assert_send_sync::<sync::Gate>();
+    assert_send_sync::<sync::Mutex<()>>();
+    assert_send_sync::<sync::MutexGuard<'_, ()>>();
+    assert_send_sync::<sync::OwnedMutexGuard<()>>();
+    assert_send_sync::<sync::Notify>();
+    assert_send_sync::<sync::Phaser>();
+    assert_send_sync::<sync::RwLock<()>>();
+    assert_send_sync::<sync::RwLockReadGuard<'_, ()>>();
+    assert_send_sync::<sync::RwLockWriteGuard<'_, ()>>();
+    assert_send_sync::<sync::WaitGroup>();
+    assert_send_sync::<sync::WaitGroupGuard>();
+};
 
 impl Semaphore {
+    pub(crate) fn from_inner(inner: SemaphoreInner) -> Semaphore {
+        let inner = Arc::new(inner);
+        Semaphore {
+            handle: Arc::new(Handle {
+                inner: Arc::downgrade(&inner),
+            }),
+            inner,
+        }
+    }
+
     /// Creates a new semaphore with a limit of `n` concurrent operations.
     ///
     /// # Examples
@@ -80,54 +733,165 @@ impl Semaphore {
     ///
     /// let s = Semaphore::new(5);
     /// ```
-    pub fn new(n: usize) -> Semaphore {
-        Semaphore {
-            inner: Arc::new(SemaphoreInner::new(n)),
-        }
+    pub fn new(n: Permits) -> Semaphore {
+        Semaphore::from_inner(SemaphoreInner::new(n))
+    }
+
+    /// Returns a builder for configuring a new semaphore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::builder().permits(4).build();
+    /// ```
+    pub fn builder() -> SemaphoreBuilder {
+        SemaphoreBuilder::new()
+    }
+
+    /// Creates a new semaphore with `n` permits that can never hold more than `n`.
+    ///
+    /// Adding permits beyond the limit panics, which catches release paths
+    /// that fire twice.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::bounded(1);
+    ///
+    /// s.acquire_unguarded().await.unwrap();
+    /// s.add_permits(1);
+    /// s.add_permits(1); // panics
+    /// # });
+    /// ```
+    pub fn bounded(n: Permits) -> Semaphore {
+        Semaphore::from_inner(SemaphoreInner::with_config(
+            n,
+            Config {
+                max: n,
+                ..Config::new()
+            },
+        ))
     }
 
     /// Attempts to get a permit for a concurrent operation.
     ///
-    /// Return whether permit has been acquired
+    /// Returns an error if no permit is currently available.
+    /// A [fair](SemaphoreBuilder::fair) semaphore also fails while other
+    /// tasks are queued, so the permits are left for them.
     ///
     /// # Examples
     ///
     /// ```
-    /// use async_sema::Semaphore;
+    /// use async_sema::{Semaphore, TryAcquireError};
     ///
     /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// let s = Semaphore::new(2);
     ///
-    /// s.acquire().await;
-    /// s.acquire().await;
+    /// s.acquire_unguarded().await.unwrap();
+    /// s.acquire_unguarded().await.unwrap();
     ///
-    /// assert!(!s.try_acquire());
+    /// assert_eq!(s.try_acquire(), Err(TryAcquireError::NoPermits));
     /// s.add_permits(1);
-    /// assert!(s.try_acquire());
+    /// assert!(s.try_acquire().is_ok());
     /// # });
     /// ```
-    pub fn try_acquire(&self) -> bool {
-        self.inner.try_acquire(1) > 0
+    pub fn try_acquire(&self) -> Result<(), TryAcquireError> {
+        self.inner.try_acquire_exact(1)?;
+        self.inner.forget_permits(1);
+        Ok(())
+    }
+
+    /// Attempts to get multiple permits for a concurrent operation.
+    ///
+    /// Either all `count` permits are acquired or none of them are.
+    /// Returns an error if not enough permits are currently available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::new(2);
+    ///
+    /// assert!(s.try_acquire_many(3).is_err());
+    /// assert!(s.try_acquire_many(2).is_ok());
+    /// assert!(s.try_acquire().is_err());
+    /// ```
+    pub fn try_acquire_many(&self, count: Permits) -> Result<(), TryAcquireError> {
+        self.inner.try_acquire_exact(count)?;
+        self.inner.forget_permits(count);
+        Ok(())
+    }
+
+    /// Attempts to get up to `n` permits for a concurrent operation.
+    ///
+    /// Takes whatever is currently free, up to `n`, and returns it as a guard.
+    /// Returns an error if no permit is currently available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, TryAcquireError};
+    ///
+    /// let s = Semaphore::new(3);
+    ///
+    /// let guard = s.try_acquire_upto(5).unwrap();
+    /// assert_eq!(guard.num_permits(), 3);
+    /// assert_eq!(s.try_acquire_upto(5).unwrap_err(), TryAcquireError::NoPermits);
+    /// ```
+    pub fn try_acquire_upto(&self, n: Permits) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        if self.inner.is_closed() {
+            return Err(TryAcquireError::Closed);
+        }
+        if self.inner.is_poisoned() {
+            return Err(TryAcquireError::Poisoned);
+        }
+
+        if !self.inner.may_take() {
+            return Err(TryAcquireError::NoPermits);
+        }
+        match self.inner.try_acquire(n) {
+            0 if n > 0 => Err(TryAcquireError::NoPermits),
+            permits => Ok(SemaphorePermit {
+                inner: &self.inner,
+                permits,
+                hold: Hold::none(),
+            }),
+        }
     }
 
     /// Waits for a permit for a concurrent operation.
     ///
+    /// Returns a guard that releases the permit when dropped.
+    ///
     /// # Examples
     ///
     /// ```
     /// use async_sema::Semaphore;
     ///
     /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
-    /// let s = Semaphore::new(2);
+    /// let s = Semaphore::new(1);
+    ///
+    /// let guard = s.acquire().await.unwrap();
+    /// assert!(s.try_acquire().is_err());
     ///
-    /// s.acquire().await;
+    /// drop(guard);
+    /// assert!(s.try_acquire().is_ok());
     /// # });
     /// ```
-    pub async fn acquire(&self) {
-        self.inner.acquire(1).await
+    pub fn acquire(&self) -> Acquire<'_> {
+        Acquire::new(&self.inner, 1)
     }
 
-    /// Waits for multiple permit for a concurrent operation.
+    /// Waits for a permit for a concurrent operation.
+    ///
+    /// Unlike `acquire`, the returned guard holds its own handle to the
+    /// semaphore, so it can be moved into a `'static` task.
     ///
     /// # Examples
     ///
@@ -135,29 +899,1256 @@ impl Semaphore {
     /// use async_sema::Semaphore;
     ///
     /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
-    /// let s = Semaphore::new(2);
+    /// let s = Semaphore::new(1);
     ///
-    /// s.batch_acquire(1).await;
+    /// let guard = s.acquire_owned().await.unwrap();
+    /// tokio::spawn(async move {
+    ///     drop(guard);
+    /// })
+    /// .await
+    /// .unwrap();
+    ///
+    /// assert!(s.try_acquire().is_ok());
     /// # });
     /// ```
-    pub async fn batch_acquire(&self, count: usize) {
-        self.inner.acquire(count).await
+    pub async fn acquire_owned(&self) -> Result<OwnedSemaphorePermit, AcquireError> {
+        let inner = self.inner.clone();
+        inner.acquire(1).await?;
+        let hold = poll_fn(|cx| Poll::Ready(Hold::new(&inner, cx.waker(), 1))).await;
+        Ok(OwnedSemaphorePermit {
+            inner,
+            permits: 1,
+            hold,
+        })
     }
 
-    /// Add permit for a concurrent operations
+    /// Waits for `count` permits, ahead of waiters with a lower priority.
+    ///
+    /// Queued tasks are served from the highest `priority` to the lowest, and
+    /// in the configured [`WakeOrder`] among equal priorities. Plain acquires
+    /// use priority `0`.
     ///
     /// # Examples
     ///
     /// ```
     /// use async_sema::Semaphore;
     ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
     /// let s = Semaphore::new(0);
     ///
-    /// assert!(!s.try_acquire());
+    /// let batch = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.acquire_unguarded().await }
+    /// });
+    /// while s.num_waiters() == 0 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    ///
+    /// let interactive = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.acquire_with_priority(1, 10).await.map(|p| p.forget()) }
+    /// });
+    /// while s.num_waiters() < 2 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    ///
+    /// // The interactive task is served first although it queued last.
+    /// s.add_permits(1);
+    /// interactive.await.unwrap().unwrap();
+    /// assert_eq!(s.num_waiters(), 1);
+    ///
     /// s.add_permits(1);
-    /// assert!(s.try_acquire());
+    /// batch.await.unwrap().unwrap();
+    /// # });
     /// ```
-    pub fn add_permits(&self, n: usize) {
-        self.inner.add_permits(n)
+    pub fn acquire_with_priority(&self, count: Permits, priority: u8) -> Acquire<'_> {
+        Acquire::new(&self.inner, count).with_priority(priority)
+    }
+
+    /// Waits for at least `min` permits, taking up to `max` if they are available.
+    ///
+    /// Resolves as soon as `min` permits can be acquired. The returned guard
+    /// reports how many permits were actually obtained via `num_permits`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(5);
+    ///
+    /// let batch = s.acquire_at_least(1, 32).await.unwrap();
+    /// assert_eq!(batch.num_permits(), 5);
+    ///
+    /// s.add_permits(2);
+    /// let batch = s.acquire_at_least(1, 32).await.unwrap();
+    /// assert_eq!(batch.num_permits(), 2);
+    /// # });
+    /// ```
+    pub fn acquire_at_least(&self, min: Permits, max: Permits) -> Acquire<'_> {
+        assert!(min <= max, "min permits must not exceed max permits");
+        Acquire::range(&self.inner, min, max)
+    }
+
+    /// Waits until every permit of the semaphore can be acquired.
+    ///
+    /// Returns a guard holding the whole capacity, so no other operation can
+    /// start until it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(4);
+    ///
+    /// let all = s.acquire_all().await.unwrap();
+    /// assert_eq!(all.num_permits(), 4);
+    /// assert!(s.try_acquire().is_err());
+    ///
+    /// drop(all);
+    /// assert_eq!(s.available_permits(), 4);
+    /// # });
+    /// ```
+    pub fn acquire_all(&self) -> Acquire<'_> {
+        self.acquire_many(self.inner.capacity())
+    }
+
+    /// Waits for `count` permits that are returned automatically after `ttl`
+    /// unless the lease is renewed.
+    ///
+    /// See [`Lease`] for details.
+    pub async fn acquire_lease(
+        &self,
+        count: Permits,
+        ttl: Duration,
+    ) -> Result<Lease, AcquireError> {
+        self.inner.acquire(count).await?;
+        Ok(Lease::new(self.inner.clone(), count, ttl))
+    }
+
+    /// Waits for `count` permits and reserves them for work that is still
+    /// being prepared.
+    ///
+    /// See [`Reservation`] for committing or rolling back the permits.
+    pub async fn reserve(&self, count: Permits) -> Result<Reservation<'_>, AcquireError> {
+        self.inner.acquire(count).await?;
+        Ok(Reservation::new(&self.inner, count))
+    }
+
+    /// Waits for `count` permits, giving up after `timeout`.
+    ///
+    /// Returns a `Timeout` error if the permits could not be acquired in time.
+    /// The timer runs on a background thread, so this works with any runtime.
+    /// With the `tokio` feature, the timer of the current tokio runtime is
+    /// used when there is one, so the timeout follows paused time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{AcquireError, Semaphore};
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(1);
+    ///
+    /// let guard = s.acquire_timeout(1, Duration::from_secs(1)).await.unwrap();
+    /// let res = s.acquire_timeout(1, Duration::from_millis(10)).await;
+    /// assert_eq!(res.unwrap_err(), AcquireError::Timeout);
+    /// # drop(guard);
+    /// # });
+    /// ```
+    pub async fn acquire_timeout(
+        &self,
+        count: Permits,
+        timeout: Duration,
+    ) -> Result<SemaphorePermit<'_>, AcquireError> {
+        match time::now().checked_add(timeout) {
+            Some(deadline) => self.acquire_until(count, deadline).await,
+            None => self.acquire_many(count).await,
+        }
+    }
+
+    /// Waits for `count` permits, giving up once `deadline` has passed.
+    ///
+    /// Returns a `Timeout` error if the permits could not be acquired in time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{AcquireError, Semaphore};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(0);
+    ///
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// let res = s.acquire_until(1, deadline).await;
+    /// assert_eq!(res.unwrap_err(), AcquireError::Timeout);
+    /// # });
+    /// ```
+    pub async fn acquire_until(
+        &self,
+        count: Permits,
+        deadline: Instant,
+    ) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.acquire_many(count).until(deadline).await
+    }
+
+    /// Waits for `count` permits until `token` is cancelled.
+    ///
+    /// Returns a `Cancelled` error if the token is cancelled first. A token
+    /// that is already cancelled fails the acquire even if permits are
+    /// available.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{AcquireError, CancellationToken, Semaphore};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(0);
+    /// let token = CancellationToken::new();
+    ///
+    /// let waiter = tokio::spawn({
+    ///     let s = s.clone();
+    ///     let token = token.clone();
+    ///     async move { s.acquire_cancellable(1, &token).await.map(drop) }
+    /// });
+    ///
+    /// token.cancel();
+    /// assert_eq!(waiter.await.unwrap(), Err(AcquireError::Cancelled));
+    /// assert_eq!(s.num_waiters(), 0);
+    /// # });
+    /// ```
+    pub async fn acquire_cancellable(
+        &self,
+        count: Permits,
+        token: &CancellationToken,
+    ) -> Result<SemaphorePermit<'_>, AcquireError> {
+        cancel::until_cancelled(token, self.acquire_many(count))
+            .await
+            .unwrap_or(Err(AcquireError::Cancelled))
+    }
+
+    /// Polls for `count` permits from a manual `Future` or `Stream` implementation.
+    ///
+    /// Either all `count` permits are acquired or none of them are. When
+    /// `Pending` is returned, the task is woken once enough permits are
+    /// released or the semaphore is closed, and should poll again.
+    ///
+    /// A release only wakes as many polling tasks as its permits can satisfy,
+    /// in the order they started polling. A woken task that stops polling
+    /// holds back the tasks behind it until the next release.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    /// use std::future::poll_fn;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(0);
+    ///
+    /// let waiter = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { poll_fn(|cx| s.poll_acquire(cx, 2)).await.map(|p| p.num_permits()) }
+    /// });
+    ///
+    /// s.add_permits(2);
+    /// assert_eq!(waiter.await.unwrap(), Ok(2));
+    /// # });
+    /// ```
+    ///
+    /// Releasing a single permit wakes only one of two polling tasks:
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use std::task::{Context, Wake, Waker};
+    ///
+    /// #[derive(Default)]
+    /// struct Count(AtomicUsize);
+    ///
+    /// impl Wake for Count {
+    ///     fn wake(self: Arc<Self>) {
+    ///         self.0.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// let s = Semaphore::new(0);
+    /// let (a, b) = (Arc::new(Count::default()), Arc::new(Count::default()));
+    /// let (wa, wb) = (Waker::from(a.clone()), Waker::from(b.clone()));
+    ///
+    /// assert!(s.poll_acquire(&mut Context::from_waker(&wa), 1).is_pending());
+    /// assert!(s.poll_acquire(&mut Context::from_waker(&wb), 1).is_pending());
+    ///
+    /// s.add_permits(1);
+    /// assert_eq!(a.0.load(Ordering::SeqCst), 1);
+    /// assert_eq!(b.0.load(Ordering::SeqCst), 0);
+    /// ```
+    pub fn poll_acquire(
+        &self,
+        cx: &mut Context<'_>,
+        count: Permits,
+    ) -> Poll<Result<SemaphorePermit<'_>, AcquireError>> {
+        ready!(self.inner.poll_acquire(cx, count))?;
+        Poll::Ready(Ok(SemaphorePermit {
+            inner: &self.inner,
+            permits: count,
+            hold: Hold::new(&self.inner, cx.waker(), count),
+        }))
+    }
+
+    /// Blocks the current thread until a permit is acquired.
+    ///
+    /// This does not need an async runtime, but it must not be called from
+    /// within an async task, as it would block the executor thread. See
+    /// [`blocking_acquire_many`](Semaphore::blocking_acquire_many) for tokio.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::new(1);
+    ///
+    /// std::thread::scope(|scope| {
+    ///     let guard = s.blocking_acquire().unwrap();
+    ///     scope.spawn(move || drop(guard));
+    ///
+    ///     let guard = s.blocking_acquire().unwrap();
+    ///     assert_eq!(guard.num_permits(), 1);
+    /// });
+    /// ```
+    pub fn blocking_acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.blocking_acquire_many(1)
+    }
+
+    /// Blocks the current thread until `count` permits are acquired.
+    ///
+    /// This does not need an async runtime, but it must not be called from
+    /// within an async task, as it would block the executor thread. With the
+    /// `tokio` feature, a worker of a multi-threaded tokio runtime calls
+    /// `block_in_place` first, so its other tasks move to other workers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::new(0);
+    ///
+    /// std::thread::spawn({
+    ///     let s = s.clone();
+    ///     move || s.add_permits(2)
+    /// });
+    ///
+    /// let guard = s.blocking_acquire_many(2).unwrap();
+    /// assert_eq!(guard.num_permits(), 2);
+    /// ```
+    pub fn blocking_acquire_many(
+        &self,
+        count: Permits,
+    ) -> Result<SemaphorePermit<'_>, AcquireError> {
+        blocking::block_on(self.acquire_many(count))
+    }
+
+    /// Runs `f` while holding a permit.
+    ///
+    /// The permit is released once the future returned by `f` completes or is
+    /// dropped, whichever happens first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(1);
+    ///
+    /// let res = s
+    ///     .with_permit(|| async {
+    ///         assert_eq!(s.available_permits(), 0);
+    ///         "done".parse::<u32>()
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// assert!(res.is_err());
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    pub async fn with_permit<F, Fut>(&self, f: F) -> Result<Fut::Output, AcquireError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future,
+    {
+        self.with_permits(1, f).await
+    }
+
+    /// Runs `f` while holding `count` permits.
+    ///
+    /// The permits are released once the future returned by `f` completes or
+    /// is dropped, whichever happens first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(4);
+    ///
+    /// let n = s.with_permits(3, || async { s.available_permits() }).await;
+    /// assert_eq!(n, Ok(1));
+    /// assert_eq!(s.available_permits(), 4);
+    /// # });
+    /// ```
+    pub async fn with_permits<F, Fut>(
+        &self,
+        count: Permits,
+        f: F,
+    ) -> Result<Fut::Output, AcquireError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future,
+    {
+        let _permit = self.acquire_many(count).await?;
+        Ok(f().await)
+    }
+
+    /// Waits for a permit for a concurrent operation without returning a guard.
+    ///
+    /// The permit is not released automatically, call `add_permits` to give it back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(1);
+    ///
+    /// s.acquire_unguarded().await.unwrap();
+    /// assert!(s.try_acquire().is_err());
+    ///
+    /// s.add_permits(1);
+    /// assert!(s.try_acquire().is_ok());
+    /// # });
+    /// ```
+    pub async fn acquire_unguarded(&self) -> Result<(), AcquireError> {
+        self.inner.acquire(1).await?;
+        self.inner.forget_permits(1);
+        Ok(())
+    }
+
+    /// Waits for multiple permit for a concurrent operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// s.batch_acquire(1).await.unwrap();
+    /// # });
+    /// ```
+    pub async fn batch_acquire(&self, count: Permits) -> Result<(), AcquireError> {
+        self.inner.acquire(count).await?;
+        self.inner.forget_permits(count);
+        Ok(())
+    }
+
+    /// Waits for multiple permits for a concurrent operation.
+    ///
+    /// Returns a guard that releases all of the permits when dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(3);
+    ///
+    /// let guard = s.acquire_many(3).await.unwrap();
+    /// assert_eq!(guard.num_permits(), 3);
+    /// assert!(s.try_acquire().is_err());
+    ///
+    /// drop(guard);
+    /// s.batch_acquire(3).await.unwrap();
+    /// # });
+    /// ```
+    ///
+    /// The permits are acquired all at once. A waiting task never holds on to
+    /// part of its request, so large requests cannot wedge each other by
+    /// each taking a share of the pool:
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(0);
+    ///
+    /// let tasks: Vec<_> = (0..2)
+    ///     .map(|_| {
+    ///         let s = s.clone();
+    ///         tokio::spawn(async move { s.batch_acquire(4).await.unwrap() })
+    ///     })
+    ///     .collect();
+    /// while s.num_waiters() < 2 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    ///
+    /// // Released permits stay available until one request can be served in full.
+    /// s.add_permits(3);
+    /// assert_eq!(s.available_permits(), 3);
+    ///
+    /// s.add_permits(1);
+    /// while s.num_waiters() > 1 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    /// assert_eq!(s.available_permits(), 0);
+    ///
+    /// s.add_permits(4);
+    /// for task in tasks {
+    ///     task.await.unwrap();
+    /// }
+    /// # });
+    /// ```
+    pub fn acquire_many(&self, count: Permits) -> Acquire<'_> {
+        Acquire::new(&self.inner, count)
+    }
+
+    /// Add permit for a concurrent operations
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::new(0);
+    ///
+    /// assert!(s.try_acquire().is_err());
+    /// s.add_permits(1);
+    /// assert!(s.try_acquire().is_ok());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the semaphore was created with [`Semaphore::bounded`] and the
+    /// permits would exceed its maximum.
+    ///
+    /// # Overflow
+    ///
+    /// The number of permits can never exceed [`Permits::MAX`]. Adding beyond
+    /// that saturates at the maximum, in debug and release builds alike.
+    ///
+    /// ```
+    /// use async_sema::{Permits, Semaphore};
+    ///
+    /// let s = Semaphore::new(Permits::MAX - 1);
+    /// s.add_permits(1);
+    /// assert_eq!(s.available_permits(), Permits::MAX);
+    ///
+    /// s.add_permits(1);
+    /// assert_eq!(s.available_permits(), Permits::MAX);
+    /// ```
+    pub fn add_permits(&self, n: Permits) {
+        self.inner.add_permits(n)
+    }
+
+    /// Sets the total number of permits managed by the semaphore.
+    ///
+    /// Growing takes effect immediately. When shrinking, available permits
+    /// are removed right away and the rest are absorbed as held permits are
+    /// released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(4);
+    ///
+    /// let guard = s.acquire_many(3).await.unwrap();
+    /// s.set_permits(2);
+    /// assert_eq!(s.available_permits(), 0);
+    ///
+    /// drop(guard);
+    /// assert_eq!(s.available_permits(), 2);
+    ///
+    /// s.set_permits(5);
+    /// assert_eq!(s.available_permits(), 5);
+    /// # });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds the maximum of a bounded semaphore.
+    pub fn set_permits(&self, n: Permits) {
+        self.inner.set_permits(n)
+    }
+
+    /// Removes `n` permits from the semaphore.
+    ///
+    /// Waits until `n` permits are available and reclaims them, so capacity
+    /// shrinks only once the permits are actually free.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(3);
+    ///
+    /// let guard = s.acquire_many(2).await.unwrap();
+    /// let remove = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.remove_permits(2).await }
+    /// });
+    ///
+    /// drop(guard);
+    /// remove.await.unwrap().unwrap();
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    pub async fn remove_permits(&self, n: Permits) -> Result<(), AcquireError> {
+        self.inner.acquire(n).await?;
+        self.inner.forget_permits(n);
+        Ok(())
+    }
+
+    /// Moves `n` permits of capacity from this semaphore to `other`.
+    ///
+    /// Waits until `n` permits are available here, then removes them and adds
+    /// them to `other`, so the combined capacity of both semaphores is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let a = Semaphore::new(4);
+    /// let b = Semaphore::new(1);
+    ///
+    /// a.transfer_to(&b, 3).await.unwrap();
+    /// assert_eq!(a.available_permits(), 1);
+    /// assert_eq!(b.available_permits(), 4);
+    /// # });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `other` is bounded and the permits would exceed its maximum.
+    pub async fn transfer_to(&self, other: &Semaphore, n: Permits) -> Result<(), AcquireError> {
+        self.inner.acquire(n).await?;
+        self.inner.forget_permits(n);
+        other.inner.add_permits(n);
+        Ok(())
+    }
+
+    /// Waits until every permit has been returned to the semaphore.
+    ///
+    /// Completes once the available permits match the configured capacity,
+    /// which makes it useful for waiting on in-flight operations during
+    /// shutdown. Closing the semaphore does not complete the wait, as held
+    /// permits stay valid until they are released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// let guard = s.acquire_owned().await.unwrap();
+    /// let worker = tokio::spawn(async move {
+    ///     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    ///     drop(guard);
+    /// });
+    ///
+    /// s.close();
+    /// s.wait_idle().await;
+    /// assert_eq!(s.available_permits(), 2);
+    /// worker.await.unwrap();
+    /// # });
+    /// ```
+    pub async fn wait_idle(&self) {
+        poll_fn(|cx| self.inner.poll_idle(cx)).await
+    }
+
+    /// Waits until at least `n` permits are available, without acquiring them.
+    ///
+    /// The permits are not reserved, so another task may take them before the
+    /// caller acts on the result. Like [`Semaphore::wait_idle`], the wait is
+    /// not interrupted by closing the semaphore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// let guard = s.acquire_owned().await.unwrap();
+    /// let watcher = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.wait_for_available(2).await }
+    /// });
+    ///
+    /// drop(guard);
+    /// watcher.await.unwrap();
+    /// assert_eq!(s.available_permits(), 2);
+    /// # });
+    /// ```
+    pub async fn wait_for_available(&self, n: Permits) {
+        poll_fn(|cx| self.inner.poll_available(cx, n)).await
+    }
+
+    /// Returns a stream of the number of available permits.
+    ///
+    /// See [`Watch`] for details.
+    pub fn watch(&self) -> Watch {
+        Watch::new(self.inner.clone())
+    }
+
+    /// Returns a stream of the number of permits of every release.
+    ///
+    /// See [`Releases`] for details.
+    pub fn releases(&self) -> Releases {
+        self.inner.releases.subscribe()
+    }
+
+    /// Returns a stream acquiring a permit each time it is polled.
+    ///
+    /// See [`PermitStream`] for details.
+    pub fn permits(&self) -> PermitStream {
+        PermitStream::new(self.clone())
+    }
+
+    /// Returns the number of permits currently available.
+    ///
+    /// The value is a snapshot and may be outdated as soon as it is returned
+    /// if other tasks acquire or release permits concurrently. Releases that
+    /// happened before a synchronization point (such as joining the releasing
+    /// task) are always visible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(3);
+    ///
+    /// let guard = s.acquire_owned().await.unwrap();
+    /// let _held = s.acquire().await.unwrap();
+    /// assert_eq!(s.available_permits(), 1);
+    ///
+    /// tokio::spawn(async move { drop(guard) }).await.unwrap();
+    /// assert_eq!(s.available_permits(), 2);
+    /// # });
+    /// ```
+    pub fn available_permits(&self) -> Permits {
+        self.inner.available_permits()
+    }
+
+    /// Returns the number of tasks currently waiting for permits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(0);
+    /// assert_eq!(s.num_waiters(), 0);
+    ///
+    /// let waiter = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.acquire().await.map(drop) }
+    /// });
+    /// while s.num_waiters() == 0 {
+    ///     tokio::task::yield_now().await;
+    /// }
+    /// assert_eq!(s.num_waiters(), 1);
+    ///
+    /// s.add_permits(1);
+    /// waiter.await.unwrap().unwrap();
+    /// assert_eq!(s.num_waiters(), 0);
+    /// # });
+    /// ```
+    pub fn num_waiters(&self) -> usize {
+        self.inner.num_waiters()
+    }
+
+    /// Closes the semaphore.
+    ///
+    /// All pending and future acquires fail with a `Closed` error.
+    /// Permits that are already held stay valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{AcquireError, Semaphore, TryAcquireError};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(0);
+    ///
+    /// let waiter = tokio::spawn({
+    ///     let s = s.clone();
+    ///     async move { s.acquire().await.map(drop) }
+    /// });
+    ///
+    /// s.close();
+    /// assert_eq!(waiter.await.unwrap(), Err(AcquireError::Closed));
+    ///
+    /// s.add_permits(1);
+    /// assert_eq!(s.try_acquire(), Err(TryAcquireError::Closed));
+    /// # });
+    /// ```
+    pub fn close(&self) {
+        self.inner.close()
+    }
+
+    /// Returns whether the semaphore has been closed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::new(1);
+    ///
+    /// assert!(!s.is_closed());
+    /// s.close();
+    /// assert!(s.is_closed());
+    /// ```
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Returns whether a permit was released while its holder was panicking.
+    ///
+    /// Only semaphores built with [`SemaphoreBuilder::poison`] become poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    /// Clears the poisoned state, so that acquires succeed again.
+    ///
+    /// Call this once the state protected by the semaphore has been checked
+    /// or restored.
+    pub fn clear_poison(&self) {
+        self.inner.clear_poison()
+    }
+
+    /// Returns the grants recorded since the last call, in the order they happened.
+    ///
+    /// Every waiting acquire is given an id when it is first polled, counting
+    /// from zero, so tests can check that permits are granted in the expected
+    /// order. Acquires made through `try_*` methods are not recorded. This is
+    /// meant for tests and requires the `grant-log` feature.
+    #[cfg(feature = "grant-log")]
+    pub fn take_grant_log(&self) -> Vec<Grant> {
+        self.inner.take_grant_log()
+    }
+
+    /// Returns a snapshot of the permits, waiters, capacity and closed state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(3);
+    /// let _guard = s.acquire().await.unwrap();
+    ///
+    /// let state = s.state();
+    /// assert_eq!(state.available, 2);
+    /// assert_eq!(state.capacity, 3);
+    /// assert_eq!(state.waiters, 0);
+    /// assert!(!state.closed);
+    /// # });
+    /// ```
+    pub fn state(&self) -> SemaphoreState {
+        self.inner.state()
+    }
+
+    /// Creates a [`WeakSemaphore`] handle that does not keep the semaphore alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// let s = Semaphore::new(1);
+    /// let weak = s.downgrade();
+    ///
+    /// assert!(weak.upgrade().is_some());
+    /// drop(s);
+    /// assert!(weak.upgrade().is_none());
+    /// ```
+    pub fn downgrade(&self) -> WeakSemaphore {
+        WeakSemaphore {
+            inner: Arc::downgrade(&self.inner),
+            handle: Arc::downgrade(&self.handle),
+        }
+    }
+
+    /// Consumes the semaphore if this is the only handle to it.
+    ///
+    /// Returns the configured capacity, with no permits outstanding. If other
+    /// clones, owned permits or streams still refer to the semaphore, it is
+    /// returned unchanged as the error. Weak handles do not count.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(3);
+    ///
+    /// let guard = s.acquire_owned().await.unwrap();
+    /// let s = s.try_unwrap().unwrap_err();
+    ///
+    /// drop(guard);
+    /// assert_eq!(s.try_unwrap().unwrap(), 3);
+    /// # });
+    /// ```
+    pub fn try_unwrap(self) -> Result<Permits, Semaphore> {
+        let Semaphore { inner, handle } = self;
+        Arc::try_unwrap(inner)
+            .map(|inner| inner.capacity())
+            .map_err(|inner| Semaphore { inner, handle })
+    }
+}
+
+/// A weak handle to a [`Semaphore`].
+///
+/// Created by [`Semaphore::downgrade`]. The handle upgrades as long as any
+/// clone of the semaphore is alive.
+#[derive(Debug, Clone, Default)]
+pub struct WeakSemaphore {
+    inner: Weak<SemaphoreInner>,
+    handle: Weak<Handle>,
+}
+
+impl WeakSemaphore {
+    /// Creates a handle that never upgrades.
+    pub fn new() -> WeakSemaphore {
+        WeakSemaphore {
+            inner: Weak::new(),
+            handle: Weak::new(),
+        }
+    }
+
+    /// Attempts to upgrade to a [`Semaphore`].
+    ///
+    /// Returns `None` if the semaphore has already been dropped.
+    pub fn upgrade(&self) -> Option<Semaphore> {
+        let handle = self.handle.upgrade()?;
+        let inner = self.inner.upgrade()?;
+        Some(Semaphore { inner, handle })
+    }
+}
+
+/// A permit acquired from a [`Semaphore`].
+///
+/// The permit is released back to the semaphore when the guard is dropped.
+#[must_use = "the permit is released as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct SemaphorePermit<'a> {
+    inner: &'a SemaphoreInner,
+    permits: Permits,
+    hold: Hold,
+}
+
+impl SemaphorePermit<'_> {
+    /// Returns the number of permits held by this guard.
+    pub fn num_permits(&self) -> Permits {
+        self.permits
+    }
+
+    /// Drops the guard without releasing its permits.
+    ///
+    /// The permits are permanently removed from the semaphore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// s.acquire().await.unwrap().forget();
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    pub fn forget(mut self) {
+        let permits = std::mem::take(&mut self.permits);
+        self.hold.release(permits);
+        self.inner.forget_permits(permits);
+    }
+
+    /// Splits `n` permits off into a new guard.
+    ///
+    /// Returns `None` if this guard holds fewer than `n` permits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(8);
+    ///
+    /// let mut guard = s.acquire_many(8).await.unwrap();
+    /// let sub = guard.split(3).unwrap();
+    /// assert_eq!(guard.num_permits(), 5);
+    /// assert_eq!(sub.num_permits(), 3);
+    /// assert!(guard.split(6).is_none());
+    ///
+    /// guard.merge(sub);
+    /// assert_eq!(guard.num_permits(), 8);
+    /// # });
+    /// ```
+    pub fn split(&mut self, n: Permits) -> Option<Self> {
+        self.permits = self.permits.checked_sub(n)?;
+        Some(SemaphorePermit {
+            inner: self.inner,
+            permits: n,
+            hold: self.hold,
+        })
+    }
+
+    /// Releases `n` of the held permits back to the semaphore right away.
+    ///
+    /// The guard keeps the remaining permits until it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(4);
+    ///
+    /// let mut guard = s.acquire_many(4).await.unwrap();
+    /// guard.release(3);
+    /// assert_eq!(guard.num_permits(), 1);
+    /// assert_eq!(s.available_permits(), 3);
+    /// # });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if this guard holds fewer than `n` permits.
+    pub fn release(&mut self, n: Permits) {
+        assert!(n <= self.permits, "releasing more permits than held");
+        self.permits -= n;
+        if n > 0 {
+            self.hold.release(n);
+            self.inner.release(n);
+        }
+    }
+
+    /// Stops attributing the permits to the task that acquired them.
+    ///
+    /// Deadlock detection takes the permits a task holds to be blocked on
+    /// whatever that task waits for next. Permits a task only acquires on
+    /// behalf of others, such as the futures of a set it polls, the unflushed
+    /// items of a sink, or a task it spawns, are not: attributed to it, they
+    /// would make the task look to be waiting on itself whenever it waits for
+    /// more permits of the same semaphore. Adapters acquiring permits like
+    /// these drop the attribution with this.
+    pub(crate) fn untracked(mut self) -> Self {
+        self.hold.release(self.permits);
+        self.hold = Hold::none();
+        self
+    }
+
+    /// Moves the permits held by `other` into this guard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `other` was acquired from a different semaphore.
+    pub fn merge(&mut self, mut other: Self) {
+        assert!(
+            std::ptr::eq(self.inner, other.inner),
+            "merging permits from different semaphores"
+        );
+        let permits = std::mem::take(&mut other.permits);
+        self.hold.take_from(&other.hold, permits);
+        self.permits += permits;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.inner.poison_if_panicking();
+        if self.permits > 0 {
+            self.hold.release(self.permits);
+            self.inner.release(self.permits);
+        }
+    }
+}
+
+/// An owned permit acquired from a [`Semaphore`].
+///
+/// The permit is released back to the semaphore when the guard is dropped.
+#[must_use = "the permit is released as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct OwnedSemaphorePermit {
+    inner: Arc<SemaphoreInner>,
+    permits: Permits,
+    hold: Hold,
+}
+
+impl OwnedSemaphorePermit {
+    /// Returns the number of permits held by this guard.
+    pub fn num_permits(&self) -> Permits {
+        self.permits
+    }
+
+    /// Drops the guard without releasing its permits.
+    ///
+    /// The permits are permanently removed from the semaphore.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// s.acquire_owned().await.unwrap().forget();
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    pub fn forget(mut self) {
+        let permits = std::mem::take(&mut self.permits);
+        self.hold.release(permits);
+        self.inner.forget_permits(permits);
+    }
+
+    /// Splits `n` permits off into a new guard.
+    ///
+    /// Returns `None` if this guard holds fewer than `n` permits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// let mut guard = s.acquire_owned().await.unwrap();
+    /// guard.merge(s.acquire_owned().await.unwrap());
+    /// assert_eq!(guard.num_permits(), 2);
+    ///
+    /// let other = guard.split(1).unwrap();
+    /// assert_eq!(guard.num_permits(), 1);
+    /// assert_eq!(other.num_permits(), 1);
+    /// assert!(guard.split(2).is_none());
+    /// # });
+    /// ```
+    pub fn split(&mut self, n: Permits) -> Option<Self> {
+        self.permits = self.permits.checked_sub(n)?;
+        Some(OwnedSemaphorePermit {
+            inner: self.inner.clone(),
+            permits: n,
+            hold: self.hold,
+        })
+    }
+
+    /// Releases `n` of the held permits back to the semaphore right away.
+    ///
+    /// The guard keeps the remaining permits until it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(4);
+    ///
+    /// let mut guard = s.acquire_owned().await.unwrap();
+    /// for _ in 0..3 {
+    ///     guard.merge(s.acquire_owned().await.unwrap());
+    /// }
+    /// guard.release(3);
+    /// assert_eq!(guard.num_permits(), 1);
+    /// assert_eq!(s.available_permits(), 3);
+    /// # });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if this guard holds fewer than `n` permits.
+    pub fn release(&mut self, n: Permits) {
+        assert!(n <= self.permits, "releasing more permits than held");
+        self.permits -= n;
+        if n > 0 {
+            self.hold.release(n);
+            self.inner.release(n);
+        }
+    }
+
+    /// Stops attributing the permits to the task that acquired them, for
+    /// permits handed on to another task.
+    ///
+    /// See [`SemaphorePermit::untracked`].
+    pub(crate) fn untracked(mut self) -> Self {
+        self.hold.release(self.permits);
+        self.hold = Hold::none();
+        self
+    }
+
+    /// Moves the permits held by `other` into this guard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `other` was acquired from a different semaphore.
+    pub fn merge(&mut self, mut other: Self) {
+        assert!(
+            Arc::ptr_eq(&self.inner, &other.inner),
+            "merging permits from different semaphores"
+        );
+        let permits = std::mem::take(&mut other.permits);
+        self.hold.take_from(&other.hold, permits);
+        self.permits += permits;
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        self.inner.poison_if_panicking();
+        if self.permits > 0 {
+            self.hold.release(self.permits);
+            self.inner.release(self.permits);
+        }
     }
 }
@@ -0,0 +1,25 @@
+//! Limiters for overload protection, built on the semaphore.
+//!
+//! Where a [`Semaphore`] only bounds how many operations run at once, the
+//! types here also bound how many may wait, or how often operations may
+//! start.
+//!
+//! [`Semaphore`]: crate::Semaphore
+
+mod aimd;
+mod breaker;
+mod bulkhead;
+mod gradient;
+mod leaky;
+mod limiter;
+mod rate;
+mod window;
+
+pub use aimd::{AimdLimiter, Outcome};
+pub use breaker::{BreakerPermit, CircuitBreaker, CircuitState};
+pub use bulkhead::Bulkhead;
+pub use gradient::GradientLimiter;
+pub use leaky::LeakyBucket;
+pub use limiter::Limiter;
+pub use rate::RateLimiter;
+pub use window::SlidingWindow;
@@ -0,0 +1,157 @@
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit};
+use std::sync::Mutex;
+
+/// The outcome of an operation, reported to an adaptive limiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The operation succeeded.
+    Success,
+    /// The operation failed in a way that signals overload, such as an
+    /// error from the backend or a timeout.
+    Overload,
+    /// The operation failed for reasons unrelated to load, and is not taken
+    /// into account.
+    Ignore,
+}
+
+/// An adaptive concurrency limit using additive increase, multiplicative
+/// decrease.
+///
+/// The limit grows by one permit for each full window of successes, that
+/// is after as many successes in a row as the current limit, and is cut by
+/// the backoff ratio on each overload. It stays between a minimum and a maximum,
+/// so an elastic backend is probed for more capacity and relieved quickly
+/// once it struggles.
+///
+/// The permits are those of a semaphore resized with
+/// [`Semaphore::set_permits`], so operations already running when the limit
+/// shrinks finish normally, and the limit is reached as they do.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::{AimdLimiter, Outcome};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let limiter = AimdLimiter::new(10, 100);
+///
+/// let permit = limiter.acquire().await.unwrap();
+/// drop(permit);
+/// limiter.record_outcome(Outcome::Overload);
+/// assert_eq!(limiter.limit(), 9);
+///
+/// for _ in 0..9 {
+///     limiter.record_outcome(Outcome::Success);
+/// }
+/// assert_eq!(limiter.limit(), 10);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct AimdLimiter {
+    sema: Semaphore,
+    min: Permits,
+    max: Permits,
+    backoff: f64,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: Permits,
+    /// Successes since the limit last changed.
+    successes: Permits,
+}
+
+impl AimdLimiter {
+    /// Creates a limiter starting at `initial` permits, growing up to `max`.
+    ///
+    /// The limit shrinks down to one permit, and by a backoff ratio of 0.9,
+    /// unless set otherwise with [`min_limit`] and [`backoff`].
+    ///
+    /// [`min_limit`]: AimdLimiter::min_limit
+    /// [`backoff`]: AimdLimiter::backoff
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` exceeds `max`.
+    pub fn new(initial: Permits, max: Permits) -> AimdLimiter {
+        assert!(initial <= max, "initial limit must not exceed the maximum");
+        AimdLimiter {
+            sema: Semaphore::new(initial),
+            min: 1.min(initial),
+            max,
+            backoff: 0.9,
+            state: Mutex::new(State {
+                limit: initial,
+                successes: 0,
+            }),
+        }
+    }
+
+    /// Sets the limit that overloads do not shrink below.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` exceeds the current limit.
+    pub fn min_limit(mut self, min: Permits) -> AimdLimiter {
+        assert!(
+            min <= self.limit(),
+            "minimum limit must not exceed the limit"
+        );
+        self.min = min;
+        self
+    }
+
+    /// Sets the ratio the limit is multiplied by on an overload.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `ratio` is between 0 and 1.
+    pub fn backoff(mut self, ratio: f64) -> AimdLimiter {
+        assert!(
+            ratio > 0.0 && ratio < 1.0,
+            "backoff ratio must be between 0 and 1"
+        );
+        self.backoff = ratio;
+        self
+    }
+
+    /// Waits for a permit under the current limit.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.sema.acquire().await
+    }
+
+    /// Adjusts the limit to the outcome of an operation.
+    pub fn record_outcome(&self, outcome: Outcome) {
+        let mut state = self.state.lock().unwrap();
+        let old = state.limit;
+        match outcome {
+            Outcome::Success => {
+                state.successes += 1;
+                if state.successes < state.limit {
+                    return;
+                }
+                state.limit = (state.limit + 1).min(self.max);
+            }
+            Outcome::Overload => {
+                let shrunk = (state.limit as f64 * self.backoff) as Permits;
+                state.limit = shrunk.max(self.min);
+            }
+            Outcome::Ignore => return,
+        }
+        state.successes = 0;
+        if state.limit != old {
+            self.sema.set_permits(state.limit);
+        }
+    }
+
+    /// Returns the current limit.
+    pub fn limit(&self) -> Permits {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Returns the semaphore holding the permits.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+}
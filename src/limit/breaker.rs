@@ -0,0 +1,259 @@
+use crate::limit::Outcome;
+use crate::time;
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit};
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The state of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Operations are admitted through the semaphore.
+    Closed,
+    /// Operations are rejected until the cooldown has passed.
+    Open,
+    /// The cooldown has passed, and a single operation is let through to
+    /// probe whether the backend has recovered.
+    HalfOpen,
+}
+
+/// A concurrency limit that stops admitting operations after repeated
+/// failures.
+///
+/// While closed, the breaker admits operations through its semaphore and
+/// counts the overloads reported in a row. Once `threshold` have been
+/// reported it opens, and rejects every acquire with
+/// [`AcquireError::Rejected`] for the `cooldown`, giving the backend time to
+/// recover. After the cooldown a single probe is admitted. If it succeeds
+/// the breaker closes again, and if it fails the breaker opens for another
+/// cooldown.
+///
+/// Outcomes are reported with [`BreakerPermit::record_outcome`], which ties
+/// each outcome to the operation it belongs to. Only the probe can close a
+/// half-open breaker, and outcomes of operations admitted before the breaker
+/// opened do not cut its cooldown short. A probe whose outcome is not
+/// reported within a cooldown is taken to have failed, so a lost probe opens
+/// the breaker again rather than keeping it half-open forever.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::{CircuitBreaker, CircuitState, Outcome};
+/// use async_sema::AcquireError;
+/// use std::time::Duration;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let breaker = CircuitBreaker::new(4, 2, Duration::from_millis(20));
+///
+/// for _ in 0..2 {
+///     let permit = breaker.acquire().await.unwrap();
+///     permit.record_outcome(Outcome::Overload);
+/// }
+/// assert_eq!(breaker.state(), CircuitState::Open);
+/// assert_eq!(breaker.acquire().await.unwrap_err(), AcquireError::Rejected);
+///
+/// tokio::time::sleep(Duration::from_millis(20)).await;
+/// let probe = breaker.acquire().await.unwrap();
+/// assert!(probe.is_probe());
+/// assert_eq!(breaker.state(), CircuitState::HalfOpen);
+/// assert!(breaker.acquire().await.is_err());
+///
+/// probe.record_outcome(Outcome::Success);
+/// assert_eq!(breaker.state(), CircuitState::Closed);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    sema: Semaphore,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// Holds when the probe in flight, if any, was admitted.
+    HalfOpen {
+        probe: Option<Instant>,
+    },
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker running up to `max_concurrent` operations, which
+    /// opens for `cooldown` after `threshold` overloads in a row.
+    pub fn new(max_concurrent: Permits, threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker::with_semaphore(Semaphore::new(max_concurrent), threshold, cooldown)
+    }
+
+    /// Creates a breaker admitting operations through `sema`.
+    pub fn with_semaphore(sema: Semaphore, threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            sema,
+            threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Waits for a permit, unless the breaker is open.
+    ///
+    /// Returns a `Rejected` error while the breaker is open, and while the
+    /// probe of a half-open breaker is in flight.
+    pub async fn acquire(&self) -> Result<BreakerPermit<'_>, AcquireError> {
+        let mut probe = Probe {
+            breaker: self,
+            since: self.admit()?,
+        };
+        let permit = self.sema.acquire().await?;
+        Ok(BreakerPermit {
+            permit,
+            breaker: self,
+            probe: probe.since.take(),
+        })
+    }
+
+    /// Adjusts the state of the breaker to the outcome of an operation that
+    /// holds no permit of the breaker.
+    ///
+    /// Such an outcome is never taken for the probe's, so it cannot close a
+    /// half-open breaker.
+    pub fn record_outcome(&self, outcome: Outcome) {
+        self.record(None, outcome);
+    }
+
+    /// Returns the current state of the breaker.
+    ///
+    /// An open breaker whose cooldown has passed reports itself half-open.
+    pub fn state(&self) -> CircuitState {
+        match *self.settled() {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { until } if time::now() < until => CircuitState::Open,
+            State::Open { .. } | State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Returns the semaphore limiting the running operations.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+
+    /// Returns when the acquire was admitted, if it is admitted as the probe.
+    fn admit(&self) -> Result<Option<Instant>, AcquireError> {
+        let mut state = self.settled();
+        let now = time::now();
+        match *state {
+            State::Closed { .. } => Ok(None),
+            State::Open { until } if now < until => Err(AcquireError::Rejected),
+            State::Open { .. } | State::HalfOpen { probe: None } => {
+                *state = State::HalfOpen { probe: Some(now) };
+                Ok(Some(now))
+            }
+            State::HalfOpen { probe: Some(_) } => Err(AcquireError::Rejected),
+        }
+    }
+
+    /// Adjusts the state to the outcome of an operation, admitted as the
+    /// probe at `probe` if it was one.
+    fn record(&self, probe: Option<Instant>, outcome: Outcome) {
+        let mut state = self.settled();
+        let now = time::now();
+        let probing = matches!(
+            (&*state, probe),
+            (State::HalfOpen { probe: Some(since) }, Some(probe)) if *since == probe
+        );
+        let cooling = matches!(*state, State::Open { until } if now < until);
+        *state = match (&*state, outcome) {
+            (State::Closed { .. }, Outcome::Success) => State::Closed { failures: 0 },
+            (State::Closed { failures }, Outcome::Overload) if failures + 1 < self.threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (State::HalfOpen { .. }, Outcome::Success) if probing => State::Closed { failures: 0 },
+            // The probe told nothing, so another one may go.
+            (State::HalfOpen { .. }, Outcome::Ignore) if probing => State::HalfOpen { probe: None },
+            (_, Outcome::Overload) if !cooling => State::Open {
+                until: now + self.cooldown,
+            },
+            // Any other outcome belongs to an operation admitted before the
+            // breaker opened, or to a probe that was already failed.
+            _ => return,
+        };
+    }
+
+    /// Locks the state, failing a probe that went a whole cooldown without
+    /// an outcome, as when its permit was dropped without reporting one.
+    fn settled(&self) -> MutexGuard<'_, State> {
+        let mut state = self.state.lock().unwrap();
+        if let State::HalfOpen { probe: Some(since) } = *state {
+            let failed = since + self.cooldown;
+            if time::now() >= failed {
+                *state = State::Open {
+                    until: failed + self.cooldown,
+                };
+            }
+        }
+        state
+    }
+}
+
+/// A permit of a [`CircuitBreaker`].
+///
+/// Report the outcome of the operation with [`record_outcome`], which
+/// releases the permit. Dropping the permit releases it without an outcome.
+///
+/// [`record_outcome`]: BreakerPermit::record_outcome
+#[must_use = "the permit is released as soon as it is dropped"]
+pub struct BreakerPermit<'a> {
+    permit: SemaphorePermit<'a>,
+    breaker: &'a CircuitBreaker,
+    /// When the permit was admitted, if it was admitted as the probe.
+    probe: Option<Instant>,
+}
+
+impl BreakerPermit<'_> {
+    /// Returns whether this permit was admitted as the probe of a half-open
+    /// breaker.
+    pub fn is_probe(&self) -> bool {
+        self.probe.is_some()
+    }
+
+    /// Adjusts the state of the breaker to the outcome of the operation
+    /// holding this permit, and releases the permit.
+    pub fn record_outcome(self, outcome: Outcome) {
+        self.breaker.record(self.probe, outcome);
+    }
+}
+
+impl fmt::Debug for BreakerPermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BreakerPermit")
+            .field("permits", &self.permit.num_permits())
+            .field("probe", &self.is_probe())
+            .finish()
+    }
+}
+
+/// Lets another probe go if the probe gives up before it gets a permit.
+struct Probe<'a> {
+    breaker: &'a CircuitBreaker,
+    /// When the probe was admitted, until it gets its permit.
+    since: Option<Instant>,
+}
+
+impl Drop for Probe<'_> {
+    fn drop(&mut self) {
+        if let Some(since) = self.since {
+            let mut state = self.breaker.state.lock().unwrap();
+            if matches!(*state, State::HalfOpen { probe: Some(probe) } if probe == since) {
+                *state = State::HalfOpen { probe: None };
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit, TryAcquireError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A concurrency limit with a bounded wait queue.
+///
+/// A plain semaphore lets any number of tasks queue up during overload. A
+/// bulkhead admits up to `max_concurrent` operations and lets at most
+/// `max_queued` more wait for a turn; any acquire beyond that fails right
+/// away with [`AcquireError::Rejected`], so callers can shed load instead.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::Bulkhead;
+/// use async_sema::AcquireError;
+/// use futures::poll;
+/// use std::pin::pin;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let bulkhead = Bulkhead::new(1, 1);
+///
+/// let running = bulkhead.acquire().await.unwrap();
+/// let mut queued = pin!(bulkhead.acquire());
+/// assert!(poll!(&mut queued).is_pending());
+/// assert_eq!(bulkhead.acquire().await.unwrap_err(), AcquireError::Rejected);
+///
+/// drop(running);
+/// assert!(queued.await.is_ok());
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Bulkhead {
+    sema: Semaphore,
+    max_queued: usize,
+    queued: AtomicUsize,
+}
+
+impl Bulkhead {
+    /// Creates a bulkhead running up to `max_concurrent` operations, with
+    /// room for `max_queued` more to wait.
+    pub fn new(max_concurrent: Permits, max_queued: usize) -> Bulkhead {
+        Bulkhead::with_semaphore(Semaphore::new(max_concurrent), max_queued)
+    }
+
+    /// Creates a bulkhead admitting operations through `sema`.
+    ///
+    /// This allows configuring the semaphore with a [`SemaphoreBuilder`],
+    /// for example to serve the queue fairly.
+    ///
+    /// [`SemaphoreBuilder`]: crate::SemaphoreBuilder
+    pub fn with_semaphore(sema: Semaphore, max_queued: usize) -> Bulkhead {
+        Bulkhead {
+            sema,
+            max_queued,
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a turn, unless the wait queue is full.
+    ///
+    /// Returns a `Rejected` error if `max_queued` tasks are already waiting.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        match self.sema.try_acquire_upto(1) {
+            Ok(permit) => return Ok(permit),
+            Err(TryAcquireError::NoPermits) => {}
+            Err(TryAcquireError::Poisoned) => return Err(AcquireError::Poisoned),
+            Err(_) => return Err(AcquireError::Closed),
+        }
+
+        self.queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < self.max_queued).then_some(n + 1)
+            })
+            .map_err(|_| AcquireError::Rejected)?;
+        let _queued = Queued(&self.queued);
+        self.sema.acquire().await
+    }
+
+    /// Takes a turn if one is free right away.
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.sema.try_acquire_upto(1)
+    }
+
+    /// Returns the number of tasks waiting for a turn.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Acquire)
+    }
+
+    /// Returns the maximum number of tasks that may wait.
+    pub fn max_queued(&self) -> usize {
+        self.max_queued
+    }
+
+    /// Returns the semaphore limiting the running operations.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+}
+
+/// Frees a place in the wait queue when dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::limit::Outcome;
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// An adaptive concurrency limit following the latency gradient.
+///
+/// The limiter keeps the lowest latency seen as a baseline for an unloaded
+/// backend. Each sample is compared to it: while latencies stay near the
+/// baseline the limit grows by a margin of the square root of the limit,
+/// and as latencies rise above it, a sign of requests queueing downstream,
+/// the limit is scaled down by the ratio of the two, to half at most. The
+/// change is smoothed over several samples.
+///
+/// This follows the gradient limiters of Netflix's concurrency-limits. As
+/// with [`AimdLimiter`], the permits are those of a semaphore resized with
+/// [`Semaphore::set_permits`].
+///
+/// [`AimdLimiter`]: crate::limit::AimdLimiter
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::{GradientLimiter, Outcome};
+/// use std::time::Duration;
+///
+/// let limiter = GradientLimiter::new(20, 1000);
+///
+/// for _ in 0..10 {
+///     limiter.record(Outcome::Success, Duration::from_millis(10));
+/// }
+/// let grown = limiter.limit();
+/// assert!(grown > 20);
+///
+/// // Latencies have tripled, so requests are queueing downstream.
+/// for _ in 0..10 {
+///     limiter.record(Outcome::Success, Duration::from_millis(30));
+/// }
+/// assert!(limiter.limit() < grown);
+/// ```
+#[derive(Debug)]
+pub struct GradientLimiter {
+    sema: Semaphore,
+    min: Permits,
+    max: Permits,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    baseline: Option<Duration>,
+}
+
+/// How much of the newly computed limit each sample moves the limit by.
+const SMOOTHING: f64 = 0.2;
+
+impl GradientLimiter {
+    /// Creates a limiter starting at `initial` permits, growing up to `max`.
+    ///
+    /// The limit shrinks down to one permit, unless set otherwise with
+    /// [`min_limit`].
+    ///
+    /// [`min_limit`]: GradientLimiter::min_limit
+    ///
+    /// # Panics
+    ///
+    /// Panics if `initial` exceeds `max`.
+    pub fn new(initial: Permits, max: Permits) -> GradientLimiter {
+        assert!(initial <= max, "initial limit must not exceed the maximum");
+        GradientLimiter {
+            sema: Semaphore::new(initial),
+            min: 1.min(initial),
+            max,
+            state: Mutex::new(State {
+                limit: initial as f64,
+                baseline: None,
+            }),
+        }
+    }
+
+    /// Sets the limit that rising latencies do not shrink below.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` exceeds the current limit.
+    pub fn min_limit(mut self, min: Permits) -> GradientLimiter {
+        assert!(
+            min <= self.limit(),
+            "minimum limit must not exceed the limit"
+        );
+        self.min = min;
+        self
+    }
+
+    /// Waits for a permit under the current limit.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.sema.acquire().await
+    }
+
+    /// Adjusts the limit to an operation that took `latency`.
+    ///
+    /// An overload counts as the steepest gradient whatever its latency, as
+    /// the latency of a failed operation says little about the backend.
+    pub fn record(&self, outcome: Outcome, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let gradient = match outcome {
+            Outcome::Success => {
+                let baseline = state.baseline.map_or(latency, |b| b.min(latency));
+                state.baseline = Some(baseline);
+                if latency.is_zero() {
+                    1.0
+                } else {
+                    (baseline.as_secs_f64() / latency.as_secs_f64()).clamp(0.5, 1.0)
+                }
+            }
+            Outcome::Overload => 0.5,
+            Outcome::Ignore => return,
+        };
+
+        let old = state.limit as Permits;
+        let target = state.limit * gradient + state.limit.sqrt();
+        let limit = state.limit * (1.0 - SMOOTHING) + target * SMOOTHING;
+        state.limit = limit.clamp(self.min as f64, self.max as f64);
+        let new = state.limit as Permits;
+        if new != old {
+            self.sema.set_permits(new);
+        }
+    }
+
+    /// Forgets the baseline latency, so it is learned again from the next
+    /// samples.
+    ///
+    /// Useful when the backend changed, for example after a deployment,
+    /// and its unloaded latency may have grown.
+    pub fn reset_baseline(&self) {
+        self.state.lock().unwrap().baseline = None;
+    }
+
+    /// Returns the current limit.
+    pub fn limit(&self) -> Permits {
+        self.state.lock().unwrap().limit as Permits
+    }
+
+    /// Returns the lowest latency seen since the baseline was last reset.
+    pub fn baseline(&self) -> Option<Duration> {
+        self.state.lock().unwrap().baseline
+    }
+
+    /// Returns the semaphore holding the permits.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+}
@@ -0,0 +1,146 @@
+use crate::time::{self, Sleep};
+use crate::{Config, SemaphoreInner, TryAcquireError};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A leaky-bucket rate limiter.
+///
+/// Unlike a [`RateLimiter`], which lets a burst through after a quiet period,
+/// a leaky bucket spaces every operation at least `interval` after the one
+/// before, so an upstream sees a smooth pace.
+///
+/// Waiting tasks queue on a fair semaphore with a single permit. The task at
+/// the head of the queue holds the permit while it sleeps until its slot,
+/// and hands it on once its operation is let through.
+///
+/// [`RateLimiter`]: crate::limit::RateLimiter
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::LeakyBucket;
+/// use std::time::{Duration, Instant};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let bucket = LeakyBucket::new(Duration::from_millis(10));
+///
+/// let start = Instant::now();
+/// for _ in 0..5 {
+///     bucket.acquire().await;
+/// }
+/// // The first goes through right away, the others 10ms apart.
+/// assert!(start.elapsed() >= Duration::from_millis(40));
+/// # });
+/// ```
+pub struct LeakyBucket {
+    turn: SemaphoreInner,
+    interval: Duration,
+    /// The earliest time the next operation may go through.
+    next: Mutex<Instant>,
+}
+
+impl LeakyBucket {
+    /// Creates a limiter letting an operation through every `interval`.
+    pub fn new(interval: Duration) -> LeakyBucket {
+        LeakyBucket {
+            turn: SemaphoreInner::with_config(
+                1,
+                Config {
+                    fair: true,
+                    ..Config::new()
+                },
+            ),
+            interval,
+            next: Mutex::new(time::now()),
+        }
+    }
+
+    /// Creates a limiter letting `rate` operations through per second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero, or above one billion, as operations are
+    /// spaced at most a nanosecond apart.
+    pub fn per_second(rate: u32) -> LeakyBucket {
+        assert!(rate > 0, "leaky bucket rate must be positive");
+        assert!(
+            rate <= 1_000_000_000,
+            "leaky bucket rate must be at most one billion per second"
+        );
+        LeakyBucket::new(Duration::from_secs(1) / rate)
+    }
+
+    /// Waits for the next slot.
+    ///
+    /// Dropping the future gives up its place in the queue, and the slot
+    /// goes to the next task.
+    pub async fn acquire(&self) {
+        // The semaphore is private to the limiter and never closed.
+        let _ = self.turn.acquire(1).await;
+        let _turn = Turn(&self.turn);
+
+        let next = *self.next.lock().unwrap();
+        Sleep::until(next).await;
+        self.take(next);
+    }
+
+    /// Takes the next slot if it is due and no task is waiting for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::limit::LeakyBucket;
+    /// use std::time::Duration;
+    ///
+    /// let bucket = LeakyBucket::new(Duration::from_secs(60));
+    ///
+    /// assert!(bucket.try_acquire().is_ok());
+    /// assert!(bucket.try_acquire().is_err());
+    /// ```
+    pub fn try_acquire(&self) -> Result<(), TryAcquireError> {
+        self.turn.try_acquire_exact(1)?;
+        let _turn = Turn(&self.turn);
+
+        let next = *self.next.lock().unwrap();
+        if time::now() < next {
+            return Err(TryAcquireError::NoPermits);
+        }
+        self.take(next);
+        Ok(())
+    }
+
+    /// Returns the interval between operations.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the number of tasks waiting for a slot.
+    pub fn num_waiters(&self) -> usize {
+        self.turn.num_waiters()
+    }
+
+    /// Schedules the slot after the one due at `next`.
+    fn take(&self, next: Instant) {
+        // After a quiet period the pace starts over from now.
+        *self.next.lock().unwrap() = time::now().max(next) + self.interval;
+    }
+}
+
+impl fmt::Debug for LeakyBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LeakyBucket")
+            .field("interval", &self.interval)
+            .field("waiters", &self.turn.num_waiters())
+            .finish()
+    }
+}
+
+/// Hands the turn on to the next waiting task when dropped.
+struct Turn<'a>(&'a SemaphoreInner);
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.0.release(1);
+    }
+}
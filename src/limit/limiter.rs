@@ -0,0 +1,73 @@
+use crate::limit::RateLimiter;
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit, TryAcquireError};
+
+/// A limit on both the operations in flight and the rate they start at.
+///
+/// An acquire first waits for an in-flight slot, and only then for a token
+/// of the rate limiter. Taking the slot first means no token is spent by a
+/// task that cannot start yet, so the rate is not used up by tasks queued
+/// behind the concurrency limit.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::{Limiter, RateLimiter};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let limiter = Limiter::new(2, RateLimiter::per_second(100, 10));
+///
+/// let a = limiter.acquire().await.unwrap();
+/// let b = limiter.acquire().await.unwrap();
+/// assert!(limiter.try_acquire().is_err());
+///
+/// drop(a);
+/// assert!(limiter.try_acquire().is_ok());
+/// # drop(b);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Limiter {
+    sema: Semaphore,
+    rate: RateLimiter,
+}
+
+impl Limiter {
+    /// Creates a limiter running up to `max_in_flight` operations at once,
+    /// started at the pace of `rate`.
+    pub fn new(max_in_flight: Permits, rate: RateLimiter) -> Limiter {
+        Limiter::with_semaphore(Semaphore::new(max_in_flight), rate)
+    }
+
+    /// Creates a limiter admitting operations through `sema`, started at the
+    /// pace of `rate`.
+    pub fn with_semaphore(sema: Semaphore, rate: RateLimiter) -> Limiter {
+        Limiter { sema, rate }
+    }
+
+    /// Waits for an in-flight slot and then for a rate token.
+    ///
+    /// Returns a guard that frees the slot when dropped. Dropping the future
+    /// while it waits for a token frees the slot it already holds.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        let permit = self.sema.acquire().await?;
+        self.rate.acquire().await;
+        Ok(permit)
+    }
+
+    /// Takes an in-flight slot and a rate token if both are free right away.
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let permit = self.sema.try_acquire_upto(1)?;
+        self.rate.try_acquire()?;
+        Ok(permit)
+    }
+
+    /// Returns the semaphore limiting the operations in flight.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+
+    /// Returns the rate limiter pacing the operations.
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate
+    }
+}
@@ -0,0 +1,198 @@
+use crate::time::{self, Sleep};
+use crate::{Config, Permits, SemaphoreInner, TryAcquireError};
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// A token-bucket rate limiter.
+///
+/// The bucket holds up to `burst` tokens and gains one every `interval`.
+/// Each operation takes a token, waiting for the next refill if none are
+/// left, so operations run at the refill rate on average, and in bursts of
+/// up to `burst` after a quiet period. The bucket starts out full.
+///
+/// Tokens are kept as the permits of a fair semaphore, so waiting tasks are
+/// served in the order they arrived. The limiter needs no async runtime: the
+/// refills are timed by the same timer as [`Semaphore::acquire_timeout`].
+///
+/// [`Semaphore::acquire_timeout`]: crate::Semaphore::acquire_timeout
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::RateLimiter;
+/// use std::time::{Duration, Instant};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let start = Instant::now();
+/// let limiter = RateLimiter::per_second(100, 5);
+///
+/// for _ in 0..10 {
+///     limiter.acquire().await;
+/// }
+/// // The first five use the burst, the other five wait for a refill each.
+/// assert!(start.elapsed() >= Duration::from_millis(50));
+/// # });
+/// ```
+pub struct RateLimiter {
+    tokens: SemaphoreInner,
+    interval: Duration,
+    burst: Permits,
+    /// When tokens were last added, advanced in whole intervals.
+    refilled: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter that adds a token every `interval`, holding up to
+    /// `burst` tokens.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn new(interval: Duration, burst: Permits) -> RateLimiter {
+        assert!(
+            !interval.is_zero(),
+            "rate limiter interval must be positive"
+        );
+        RateLimiter {
+            tokens: SemaphoreInner::with_config(
+                burst,
+                Config {
+                    fair: true,
+                    ..Config::new()
+                },
+            ),
+            interval,
+            burst,
+            refilled: Mutex::new(time::now()),
+        }
+    }
+
+    /// Creates a limiter allowing `rate` operations per second on average,
+    /// in bursts of up to `burst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero, or above one billion, as tokens are added
+    /// at most once a nanosecond.
+    pub fn per_second(rate: u32, burst: Permits) -> RateLimiter {
+        assert!(rate > 0, "rate limiter rate must be positive");
+        assert!(
+            rate <= 1_000_000_000,
+            "rate limiter rate must be at most one billion per second"
+        );
+        RateLimiter::new(Duration::from_secs(1) / rate, burst)
+    }
+
+    /// Waits for a token and takes it.
+    pub async fn acquire(&self) {
+        self.acquire_many(1).await
+    }
+
+    /// Waits for `n` tokens and takes them all at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds the burst size, as the bucket never holds that
+    /// many tokens.
+    pub async fn acquire_many(&self, n: Permits) {
+        assert!(n <= self.burst, "acquiring more tokens than the burst size");
+        self.refill();
+
+        let mut acquire = self.tokens.acquire(n);
+        let mut sleep: Option<Sleep> = None;
+        poll_fn(|cx| loop {
+            // The semaphore is private to the limiter and never closed.
+            if Pin::new(&mut acquire).poll(cx).is_ready() {
+                return Poll::Ready(());
+            }
+            let next = sleep.get_or_insert_with(|| Sleep::until(self.next_refill()));
+            if Pin::new(next).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            sleep = None;
+            self.refill();
+        })
+        .await
+    }
+
+    /// Takes a token if one is available right away.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::limit::RateLimiter;
+    /// use std::time::Duration;
+    ///
+    /// let limiter = RateLimiter::new(Duration::from_secs(60), 2);
+    ///
+    /// assert!(limiter.try_acquire().is_ok());
+    /// assert!(limiter.try_acquire().is_ok());
+    /// assert!(limiter.try_acquire().is_err());
+    /// ```
+    pub fn try_acquire(&self) -> Result<(), TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Takes `n` tokens if they are all available right away.
+    pub fn try_acquire_many(&self, n: Permits) -> Result<(), TryAcquireError> {
+        self.refill();
+        self.tokens.try_acquire_exact(n)
+    }
+
+    /// Returns the number of tokens available right now.
+    pub fn available_tokens(&self) -> Permits {
+        self.refill();
+        self.tokens.available_permits()
+    }
+
+    /// Returns the interval at which tokens are added.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the most tokens the bucket holds.
+    pub fn burst(&self) -> Permits {
+        self.burst
+    }
+
+    /// Adds the tokens due since the last refill, up to the burst size.
+    fn refill(&self) {
+        let mut refilled = self.refilled.lock().unwrap();
+        let now = time::now();
+        let due = now.saturating_duration_since(*refilled).as_nanos() / self.interval.as_nanos();
+        if due == 0 {
+            return;
+        }
+
+        let room = self.burst - self.tokens.available_permits().min(self.burst);
+        let added = due.min(room as u128) as Permits;
+        *refilled = if due < self.burst as u128 {
+            *refilled + Duration::from_nanos((self.interval.as_nanos() * due) as u64)
+        } else {
+            // A full bucket does not save up tokens for later.
+            now
+        };
+        drop(refilled);
+        if added > 0 {
+            self.tokens.release(added);
+        }
+    }
+
+    fn next_refill(&self) -> Instant {
+        *self.refilled.lock().unwrap() + self.interval
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("available", &self.tokens.available_permits())
+            .field("interval", &self.interval)
+            .field("burst", &self.burst)
+            .finish()
+    }
+}
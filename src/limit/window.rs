@@ -0,0 +1,186 @@
+use crate::time::{self, Sleep};
+use crate::{Config, Permits, SemaphoreInner, TryAcquireError};
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// A sliding-window rate limiter.
+///
+/// Allows up to `limit` operations in any `window` of time, such as an API
+/// quota of 600 requests per rolling minute. Each operation is logged as it
+/// starts, and counts against the limit until `window` has passed.
+///
+/// Operations can also weigh more than one, for requests that cost more of
+/// the quota. The log keeps an entry for each operation in the window, and
+/// the quota left is kept as the permits of a fair semaphore, so waiting
+/// tasks are served in the order they arrived.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::limit::SlidingWindow;
+/// use std::time::{Duration, Instant};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let start = Instant::now();
+/// let limiter = SlidingWindow::new(3, Duration::from_millis(50));
+///
+/// for _ in 0..6 {
+///     limiter.acquire().await;
+/// }
+/// // The last three wait for the first three to leave the window.
+/// assert!(start.elapsed() >= Duration::from_millis(50));
+/// # });
+/// ```
+pub struct SlidingWindow {
+    quota: SemaphoreInner,
+    limit: Permits,
+    window: Duration,
+    /// When each operation in the window started, with its weight.
+    log: Mutex<VecDeque<(Instant, Permits)>>,
+}
+
+impl SlidingWindow {
+    /// Creates a limiter allowing `limit` operations in any `window`.
+    pub fn new(limit: Permits, window: Duration) -> SlidingWindow {
+        SlidingWindow {
+            quota: SemaphoreInner::with_config(
+                limit,
+                Config {
+                    fair: true,
+                    ..Config::new()
+                },
+            ),
+            limit,
+            window,
+            log: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Waits until an operation fits in the window, and logs it.
+    pub async fn acquire(&self) {
+        self.acquire_many(1).await
+    }
+
+    /// Waits until an operation weighing `weight` fits in the window, and
+    /// logs it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` exceeds the limit, as it would never fit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::limit::SlidingWindow;
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let limiter = SlidingWindow::new(10, Duration::from_secs(60));
+    ///
+    /// limiter.acquire_many(8).await;
+    /// assert_eq!(limiter.available(), 2);
+    /// assert!(limiter.try_acquire_many(3).is_err());
+    /// # });
+    /// ```
+    pub async fn acquire_many(&self, weight: Permits) {
+        assert!(
+            weight <= self.limit,
+            "acquiring more than the sliding window limit"
+        );
+        self.expire();
+
+        let mut acquire = self.quota.acquire(weight);
+        let mut sleep: Option<Sleep> = None;
+        poll_fn(|cx| loop {
+            // The semaphore is private to the limiter and never closed.
+            if Pin::new(&mut acquire).poll(cx).is_ready() {
+                self.record(weight);
+                return Poll::Ready(());
+            }
+            let next = sleep.get_or_insert_with(|| Sleep::until(self.next_expiry()));
+            if Pin::new(next).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            sleep = None;
+            self.expire();
+        })
+        .await
+    }
+
+    /// Logs an operation if it fits in the window right away.
+    pub fn try_acquire(&self) -> Result<(), TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Logs an operation weighing `weight` if it fits in the window right away.
+    pub fn try_acquire_many(&self, weight: Permits) -> Result<(), TryAcquireError> {
+        self.expire();
+        self.quota.try_acquire_exact(weight)?;
+        self.record(weight);
+        Ok(())
+    }
+
+    /// Returns how much of the limit is left in the current window.
+    pub fn available(&self) -> Permits {
+        self.expire();
+        self.quota.available_permits()
+    }
+
+    /// Returns the most operations allowed in a window.
+    pub fn limit(&self) -> Permits {
+        self.limit
+    }
+
+    /// Returns the length of the window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn record(&self, weight: Permits) {
+        if weight > 0 {
+            self.log.lock().unwrap().push_back((time::now(), weight));
+        }
+    }
+
+    /// Gives back the quota of operations that have left the window.
+    fn expire(&self) {
+        let mut log = self.log.lock().unwrap();
+        let now = time::now();
+        let mut expired = 0;
+        while let Some(&(start, weight)) = log.front() {
+            if start + self.window > now {
+                break;
+            }
+            log.pop_front();
+            expired += weight;
+        }
+        drop(log);
+        if expired > 0 {
+            self.quota.release(expired);
+        }
+    }
+
+    /// Returns when the oldest operation leaves the window.
+    ///
+    /// With an empty log the quota is held by operations about to be logged,
+    /// which leave the window no sooner than a full window from now.
+    fn next_expiry(&self) -> Instant {
+        let log = self.log.lock().unwrap();
+        log.front().map_or_else(time::now, |&(start, _)| start) + self.window
+    }
+}
+
+impl fmt::Debug for SlidingWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlidingWindow")
+            .field("available", &self.quota.available_permits())
+            .field("limit", &self.limit)
+            .field("window", &self.window)
+            .finish()
+    }
+}
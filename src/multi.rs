@@ -0,0 +1,174 @@
+use crate::deadlock::Hold;
+use crate::{AcquireError, Permits, Semaphore, SemaphoreInner, SemaphorePermit, TryAcquireError};
+use std::sync::Arc;
+
+/// Waits for permits from several semaphores, and takes them all at once.
+///
+/// Each request names a semaphore and the number of its permits needed,
+/// such as CPU slots, database connections and a memory budget for one job.
+/// Nothing is held while any semaphore is short, so two jobs asking for the
+/// same semaphores in a different order cannot deadlock each other, as they
+/// would when acquiring one after the other. Requests for the same
+/// semaphore are added up.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::{acquire_all, Semaphore};
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let cpu = Semaphore::new(4);
+/// let db = Semaphore::new(1);
+///
+/// let job = acquire_all(&[(&cpu, 2), (&db, 1)]).await.unwrap();
+/// assert_eq!(cpu.available_permits(), 2);
+///
+/// // A second job cannot get the connection, so it takes no CPU slots.
+/// assert!(async_sema::try_acquire_all(&[(&cpu, 2), (&db, 1)]).is_err());
+/// assert_eq!(cpu.available_permits(), 2);
+///
+/// drop(job);
+/// assert_eq!(db.available_permits(), 1);
+/// # });
+/// ```
+pub async fn acquire_all<'a>(
+    requests: &[(&'a Semaphore, Permits)],
+) -> Result<MultiPermit<'a>, AcquireError> {
+    let requests = merge(requests);
+    acquire_each(&inners(&requests)).await?;
+    Ok(MultiPermit::new(&requests))
+}
+
+/// Takes permits from several semaphores if all of them have enough
+/// available right away.
+///
+/// See [`acquire_all`].
+pub fn try_acquire_all<'a>(
+    requests: &[(&'a Semaphore, Permits)],
+) -> Result<MultiPermit<'a>, TryAcquireError> {
+    let requests = merge(requests);
+    try_acquire_each(&inners(&requests), None).map_err(|(_, err)| err)?;
+    Ok(MultiPermit::new(&requests))
+}
+
+/// Adds up the requests for the same semaphore, which could otherwise never
+/// be satisfied at once when they exceed its capacity together.
+fn merge<'a>(requests: &[(&'a Semaphore, Permits)]) -> Vec<(&'a Semaphore, Permits)> {
+    let mut merged: Vec<(&Semaphore, Permits)> = Vec::with_capacity(requests.len());
+    for &(sema, n) in requests {
+        match merged
+            .iter_mut()
+            .find(|(s, _)| Arc::ptr_eq(&s.inner, &sema.inner))
+        {
+            Some((_, total)) => *total += n,
+            None => merged.push((sema, n)),
+        }
+    }
+    merged
+}
+
+fn inners<'a>(requests: &[(&'a Semaphore, Permits)]) -> Vec<(&'a SemaphoreInner, Permits)> {
+    requests
+        .iter()
+        .map(|&(sema, n)| (&*sema.inner, n))
+        .collect()
+}
+
+/// Waits until every request can be met, and takes the permits of all of
+/// them at once.
+///
+/// The task queues on the semaphore that was short like any other waiter,
+/// then holds its permits while it tries the others, and gives them back to
+/// queue on the next one that is short. Nothing is held while it waits.
+pub(crate) async fn acquire_each(
+    requests: &[(&SemaphoreInner, Permits)],
+) -> Result<(), AcquireError> {
+    let mut short = match try_acquire_each(requests, None) {
+        Ok(()) => return Ok(()),
+        Err((i, TryAcquireError::NoPermits)) => i,
+        Err((_, err)) => return Err(acquire_error(err)),
+    };
+    loop {
+        let (inner, n) = requests[short];
+        inner.acquire(n).await?;
+        match try_acquire_each(requests, Some(short)) {
+            Ok(()) => return Ok(()),
+            Err((i, err)) => {
+                inner.release(n);
+                match err {
+                    TryAcquireError::NoPermits => short = i,
+                    err => return Err(acquire_error(err)),
+                }
+            }
+        }
+    }
+}
+
+/// Takes the permits of every request but `skip`, or of none of them.
+///
+/// On failure, returns the index of the request that could not be met.
+pub(crate) fn try_acquire_each(
+    requests: &[(&SemaphoreInner, Permits)],
+    skip: Option<usize>,
+) -> Result<(), (usize, TryAcquireError)> {
+    for (i, &(inner, n)) in requests.iter().enumerate() {
+        if Some(i) == skip {
+            continue;
+        }
+        if let Err(err) = inner.try_acquire_exact(n) {
+            for (j, &(inner, n)) in requests[..i].iter().enumerate() {
+                if Some(j) != skip {
+                    inner.release(n);
+                }
+            }
+            return Err((i, err));
+        }
+    }
+    Ok(())
+}
+
+fn acquire_error(err: TryAcquireError) -> AcquireError {
+    match err {
+        TryAcquireError::Closed => AcquireError::Closed,
+        TryAcquireError::Poisoned => AcquireError::Poisoned,
+        TryAcquireError::NoPermits => unreachable!("short requests are waited for"),
+    }
+}
+
+/// Permits taken from several semaphores by [`acquire_all`].
+///
+/// Dropping the guard releases the permits of every semaphore.
+#[must_use = "the permits are released as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct MultiPermit<'a> {
+    permits: Vec<SemaphorePermit<'a>>,
+}
+
+impl<'a> MultiPermit<'a> {
+    fn new(requests: &[(&'a Semaphore, Permits)]) -> Self {
+        let permits = requests
+            .iter()
+            .map(|&(sema, permits)| SemaphorePermit {
+                inner: &sema.inner,
+                permits,
+                hold: Hold::none(),
+            })
+            .collect();
+        Self { permits }
+    }
+
+    /// Returns the number of permits held of `sema`.
+    pub fn num_permits(&self, sema: &Semaphore) -> Permits {
+        self.permits
+            .iter()
+            .filter(|p| std::ptr::eq(p.inner, &*sema.inner))
+            .map(|p| p.permits)
+            .sum()
+    }
+
+    /// Splits the guard into a guard for each semaphore, in the order they
+    /// were first requested.
+    pub fn into_permits(self) -> Vec<SemaphorePermit<'a>> {
+        self.permits
+    }
+}
@@ -0,0 +1,206 @@
+use crate::deadlock::Hold;
+use crate::{AcquireError, Permits, Semaphore, SemaphorePermit, TryAcquireError};
+use event_listener::Event;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+/// A semaphore shared by several owners, each capped at a share of it.
+///
+/// Without a cap a single noisy client can take the entire capacity. Here
+/// every acquire names its owner, and an owner holding `max_per_owner`
+/// permits waits until some of them are back before taking more, leaving
+/// the rest of the capacity to others.
+///
+/// An acquire first claims its share of the owner's cap, then waits for the
+/// permits of the semaphore, so an owner at its cap never queues on the
+/// semaphore ahead of others.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::OwnerSemaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = OwnerSemaphore::new(10, 2);
+///
+/// let a1 = s.acquire_as("noisy", 1).await.unwrap();
+/// let a2 = s.acquire_as("noisy", 1).await.unwrap();
+/// assert!(s.try_acquire_as("noisy", 1).is_err());
+///
+/// // Other clients still get permits.
+/// let b = s.acquire_as("quiet", 2).await.unwrap();
+/// assert_eq!(s.held_by(&"noisy"), 2);
+/// assert_eq!(s.semaphore().available_permits(), 6);
+/// # drop((a1, a2, b));
+/// # });
+/// ```
+pub struct OwnerSemaphore<O> {
+    sema: Semaphore,
+    max_per_owner: Permits,
+    /// The permits held or claimed by each owner with any.
+    held: Mutex<HashMap<O, Permits>>,
+    released: Event,
+}
+
+impl<O: Eq + Hash + Clone> OwnerSemaphore<O> {
+    /// Creates a semaphore with `permits` permits, of which an owner may
+    /// hold up to `max_per_owner` at once.
+    pub fn new(permits: Permits, max_per_owner: Permits) -> OwnerSemaphore<O> {
+        OwnerSemaphore::with_semaphore(Semaphore::new(permits), max_per_owner)
+    }
+
+    /// Creates a semaphore sharing the permits of `sema`, of which an owner
+    /// may hold up to `max_per_owner` at once.
+    pub fn with_semaphore(sema: Semaphore, max_per_owner: Permits) -> OwnerSemaphore<O> {
+        OwnerSemaphore {
+            sema,
+            max_per_owner,
+            held: Mutex::new(HashMap::new()),
+            released: Event::new(),
+        }
+    }
+
+    /// Waits for `n` permits on behalf of `owner`.
+    ///
+    /// Waits first until the owner holds few enough permits to take `n`
+    /// without going over its cap, then for the permits themselves.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds the cap, as the owner could never take that many.
+    pub async fn acquire_as(
+        &self,
+        owner: O,
+        n: Permits,
+    ) -> Result<OwnerPermit<'_, O>, AcquireError> {
+        assert!(
+            n <= self.max_per_owner,
+            "acquiring more permits than the cap of an owner"
+        );
+        let share = loop {
+            if let Some(share) = self.claim(&owner, n) {
+                break share;
+            }
+
+            let listener = self.released.listen();
+            if let Some(share) = self.claim(&owner, n) {
+                break share;
+            }
+            listener.await;
+        };
+        let permit = self.sema.acquire_many(n).await?;
+        Ok(OwnerPermit { share, permit })
+    }
+
+    /// Takes `n` permits on behalf of `owner`, if they are available right
+    /// away and within the owner's cap.
+    pub fn try_acquire_as(
+        &self,
+        owner: O,
+        n: Permits,
+    ) -> Result<OwnerPermit<'_, O>, TryAcquireError> {
+        let share = self.claim(&owner, n).ok_or(TryAcquireError::NoPermits)?;
+        self.sema.inner.try_acquire_exact(n)?;
+        let permit = SemaphorePermit {
+            inner: &self.sema.inner,
+            permits: n,
+            hold: Hold::none(),
+        };
+        Ok(OwnerPermit { share, permit })
+    }
+
+    /// Returns the number of permits `owner` holds.
+    pub fn held_by(&self, owner: &O) -> Permits {
+        self.held.lock().unwrap().get(owner).copied().unwrap_or(0)
+    }
+
+    /// Returns the most permits an owner may hold at once.
+    pub fn max_per_owner(&self) -> Permits {
+        self.max_per_owner
+    }
+
+    /// Returns the semaphore whose permits are shared.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+
+    /// Claims `n` permits of the owner's cap, if they fit under it.
+    fn claim(&self, owner: &O, n: Permits) -> Option<Share<'_, O>> {
+        let mut held = self.held.lock().unwrap();
+        let count = held.get(owner).copied().unwrap_or(0);
+        if count + n > self.max_per_owner {
+            return None;
+        }
+        held.insert(owner.clone(), count + n);
+        Some(Share {
+            sema: self,
+            owner: owner.clone(),
+            permits: n,
+        })
+    }
+}
+
+impl<O> fmt::Debug for OwnerSemaphore<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnerSemaphore")
+            .field("sema", &self.sema)
+            .field("max_per_owner", &self.max_per_owner)
+            .field("owners", &self.held.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// Permits taken from an [`OwnerSemaphore`] on behalf of an owner.
+///
+/// Dropping the guard releases the permits and frees the owner's share.
+#[must_use = "the permits are released as soon as the guard is dropped"]
+pub struct OwnerPermit<'a, O: Eq + Hash + Clone> {
+    // Releases the permits before the share, so an owner woken by the share
+    // finds them available.
+    permit: SemaphorePermit<'a>,
+    share: Share<'a, O>,
+}
+
+impl<O: Eq + Hash + Clone> OwnerPermit<'_, O> {
+    /// Returns the owner the permits were taken for.
+    pub fn owner(&self) -> &O {
+        &self.share.owner
+    }
+
+    /// Returns the number of permits held by this guard.
+    pub fn num_permits(&self) -> Permits {
+        self.permit.num_permits()
+    }
+}
+
+impl<O: Eq + Hash + Clone + fmt::Debug> fmt::Debug for OwnerPermit<'_, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnerPermit")
+            .field("owner", &self.share.owner)
+            .field("permits", &self.permit.num_permits())
+            .finish()
+    }
+}
+
+/// A claim on part of an owner's cap, given back when dropped.
+struct Share<'a, O: Eq + Hash + Clone> {
+    sema: &'a OwnerSemaphore<O>,
+    owner: O,
+    permits: Permits,
+}
+
+impl<O: Eq + Hash + Clone> Drop for Share<'_, O> {
+    fn drop(&mut self) {
+        let mut held = self.sema.held.lock().unwrap();
+        if let Some(count) = held.get_mut(&self.owner) {
+            *count -= self.permits;
+            if *count == 0 {
+                held.remove(&self.owner);
+            }
+        }
+        drop(held);
+        self.sema.released.notify(usize::MAX);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{AcquireError, OwnedSemaphorePermit, Semaphore};
+use futures_core::Stream;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+type AcquireOwned =
+    Pin<Box<dyn Future<Output = Result<OwnedSemaphorePermit, AcquireError>> + Send + Sync>>;
+
+/// Stream returned by [`Semaphore::permits`].
+///
+/// Yields a permit each time one is acquired, for as long as the semaphore
+/// stays open, so a dispatch loop waits for room before it takes on more
+/// work. The stream ends once the semaphore is closed or poisoned.
+///
+/// The stream holds a handle to the semaphore, which keeps it open after
+/// its other handles are dropped.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+/// use futures::StreamExt;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(2);
+/// let mut permits = s.permits();
+/// let mut workers = Vec::new();
+///
+/// for job in 0..4 {
+///     let permit = permits.next().await.unwrap();
+///     workers.push(tokio::spawn(async move {
+///         let _permit = permit;
+///         job * 2
+///     }));
+/// }
+/// for worker in workers {
+///     worker.await.unwrap();
+/// }
+///
+/// s.close();
+/// assert!(permits.next().await.is_none());
+/// # });
+/// ```
+///
+/// [`Semaphore::permits`]: crate::Semaphore::permits
+#[must_use = "streams do nothing unless polled"]
+pub struct PermitStream {
+    sema: Semaphore,
+    /// The acquire of the next permit, until the semaphore fails.
+    next: Option<AcquireOwned>,
+}
+
+impl PermitStream {
+    pub(crate) fn new(sema: Semaphore) -> Self {
+        Self {
+            next: Some(Self::acquire(sema.clone())),
+            sema,
+        }
+    }
+
+    fn acquire(sema: Semaphore) -> AcquireOwned {
+        Box::pin(async move { sema.acquire_owned().await })
+    }
+}
+
+impl Stream for PermitStream {
+    type Item = OwnedSemaphorePermit;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(next) = &mut this.next else {
+            return Poll::Ready(None);
+        };
+        match ready!(next.as_mut().poll(cx)) {
+            Ok(permit) => {
+                this.next = Some(Self::acquire(this.sema.clone()));
+                Poll::Ready(Some(permit.untracked()))
+            }
+            Err(_) => {
+                this.next = None;
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PermitStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PermitStream")
+            .field("ended", &self.next.is_none())
+            .finish()
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{Acquire, Permits, Semaphore, SemaphoreState};
+use std::ops::Index;
+
+/// Named concurrency limits for the stages of a pipeline.
+///
+/// Each stage has a semaphore of its own, so work at one stage is limited
+/// independently of the others, as when parsing may run wide but writing
+/// to disk may not. Stages are looked up by name, and the pipeline reports
+/// the work in flight across all of them.
+///
+/// Unlike a [`SemaphoreGroup`], permits do not move between stages: each
+/// permit returns to the stage it was taken from.
+///
+/// [`SemaphoreGroup`]: crate::SemaphoreGroup
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Pipeline;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let pipeline = Pipeline::new().stage("parse", 8).stage("io", 2);
+///
+/// let parsing = pipeline["parse"].acquire().await.unwrap();
+/// let writing = pipeline["io"].acquire_many(2).await.unwrap();
+/// assert!(pipeline["io"].semaphore().try_acquire().is_err());
+/// assert_eq!(pipeline.in_flight(), 3);
+///
+/// drop((parsing, writing));
+/// assert_eq!(pipeline.in_flight(), 0);
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    stages: Vec<PipelineStage>,
+}
+
+impl Pipeline {
+    /// Creates a pipeline without stages.
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    /// Adds a stage named `name`, running up to `limit` operations at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pipeline already has a stage named `name`.
+    pub fn stage(self, name: impl Into<String>, limit: Permits) -> Pipeline {
+        self.stage_with_semaphore(name, Semaphore::new(limit))
+    }
+
+    /// Adds a stage named `name`, limited by `sema`.
+    ///
+    /// The semaphore may be shared with other pipelines and tasks, all
+    /// drawing on the same limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pipeline already has a stage named `name`.
+    pub fn stage_with_semaphore(mut self, name: impl Into<String>, sema: Semaphore) -> Pipeline {
+        let name = name.into();
+        assert!(self.get(&name).is_none(), "duplicate pipeline stage {name}");
+        self.stages.push(PipelineStage { name, sema });
+        self
+    }
+
+    /// Returns the stage named `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&PipelineStage> {
+        self.stages.iter().find(|stage| stage.name == name)
+    }
+
+    /// Returns the stages, in the order they were added.
+    pub fn stages(&self) -> &[PipelineStage] {
+        &self.stages
+    }
+
+    /// Returns the number of permits held across all stages.
+    pub fn in_flight(&self) -> Permits {
+        self.stages.iter().map(PipelineStage::in_flight).sum()
+    }
+
+    /// Returns the number of tasks waiting for permits across all stages.
+    pub fn num_waiters(&self) -> usize {
+        self.stages
+            .iter()
+            .map(|stage| stage.sema.num_waiters())
+            .sum()
+    }
+
+    /// Closes the semaphore of every stage.
+    pub fn close(&self) {
+        self.stages.iter().for_each(|stage| stage.sema.close());
+    }
+}
+
+impl Index<&str> for Pipeline {
+    type Output = PipelineStage;
+
+    /// Returns the stage named `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pipeline has no stage named `name`.
+    fn index(&self, name: &str) -> &PipelineStage {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no pipeline stage {name}"))
+    }
+}
+
+/// A stage of a [`Pipeline`], with the semaphore limiting it.
+///
+/// Clones share the same semaphore, so a task may keep the stages it works
+/// at without the whole pipeline.
+#[derive(Debug, Clone)]
+pub struct PipelineStage {
+    name: String,
+    sema: Semaphore,
+}
+
+impl PipelineStage {
+    /// Returns the name of the stage.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Waits for a permit of the stage.
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.sema.acquire()
+    }
+
+    /// Waits for `count` permits of the stage.
+    pub fn acquire_many(&self, count: Permits) -> Acquire<'_> {
+        self.sema.acquire_many(count)
+    }
+
+    /// Returns the number of permits held at the stage.
+    pub fn in_flight(&self) -> Permits {
+        let state = self.sema.state();
+        state.capacity.saturating_sub(state.available)
+    }
+
+    /// Returns a snapshot of the state of the stage's semaphore.
+    pub fn state(&self) -> SemaphoreState {
+        self.sema.state()
+    }
+
+    /// Returns the semaphore limiting the stage.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+}
@@ -0,0 +1,312 @@
+//! An async pool of reusable objects.
+//!
+//! A [`Pool`] holds items such as buffers or connections, and hands each one
+//! out to a single task at a time. Tasks wait on a semaphore with a permit
+//! for each item, and the item goes back to the pool when its guard is
+//! dropped. A pool can also create its items on demand, up to a maximum,
+//! with an async initializer such as opening a connection.
+//!
+//! # Examples
+//!
+//! ```
+//! use async_sema::pool::Pool;
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let pool = Pool::from_items(vec![Vec::<u8>::with_capacity(1024); 2]);
+//!
+//! let mut buf = pool.get().await;
+//! buf.extend_from_slice(b"hello");
+//! assert_eq!(pool.available(), 1);
+//!
+//! drop(buf);
+//! assert_eq!(pool.available(), 2);
+//! # });
+//! ```
+
+use crate::{Permits, SemaphoreInner};
+use futures_core::future::BoxFuture;
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+type Init<T> = Box<dyn Fn() -> BoxFuture<'static, T> + Send + Sync>;
+
+/// A pool of items, handed out to one task at a time.
+///
+/// See the [module documentation](self) for an overview.
+pub struct Pool<T> {
+    /// One permit for each item that may be handed out, idle or not yet
+    /// created.
+    sema: SemaphoreInner,
+    items: Mutex<Items<T>>,
+    init: Option<Init<T>>,
+}
+
+struct Items<T> {
+    idle: Vec<T>,
+    /// The items a lazy pool may still create, not counting those being
+    /// created.
+    uncreated: Permits,
+}
+
+impl<T> Pool<T> {
+    /// Creates a pool of up to `max` items, each created by `init` the first
+    /// time no idle item is left.
+    ///
+    /// The semaphore gates the creation as well, so at most `max` items are
+    /// ever created or handed out at once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::pool::Pool;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let opened = Arc::new(AtomicUsize::new(0));
+    /// let pool = Pool::new(2, {
+    ///     let opened = opened.clone();
+    ///     move || {
+    ///         let opened = opened.clone();
+    ///         async move { opened.fetch_add(1, Ordering::Relaxed) }
+    ///     }
+    /// });
+    ///
+    /// let first = pool.get().await;
+    /// drop(first);
+    /// // The idle connection is reused rather than opening another one.
+    /// let (a, b) = (pool.get().await, pool.get().await);
+    /// assert_eq!((*a, *b), (0, 1));
+    /// assert_eq!(opened.load(Ordering::Relaxed), 2);
+    /// # });
+    /// ```
+    pub fn new<F, Fut>(max: Permits, init: F) -> Pool<T>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        Pool {
+            sema: SemaphoreInner::new(max),
+            items: Mutex::new(Items {
+                idle: Vec::new(),
+                uncreated: max,
+            }),
+            init: Some(Box::new(move || Box::pin(init()))),
+        }
+    }
+
+    /// Creates a pool holding `items`.
+    pub fn from_items(items: impl IntoIterator<Item = T>) -> Pool<T> {
+        let idle: Vec<T> = items.into_iter().collect();
+        Pool {
+            sema: SemaphoreInner::new(idle.len() as Permits),
+            items: Mutex::new(Items { idle, uncreated: 0 }),
+            init: None,
+        }
+    }
+
+    /// Waits for an item and takes it out of the pool.
+    ///
+    /// If no item is idle but the pool may create more, a new one is created
+    /// by the initializer. The item goes back to the pool when the guard is
+    /// dropped.
+    pub async fn get(&self) -> Pooled<'_, T> {
+        // The semaphore is private to the pool and never closed.
+        let _ = self.sema.acquire(1).await;
+        if let Some(item) = self.take_idle() {
+            return self.pooled(item);
+        }
+
+        let init = self.init.as_ref().unwrap();
+        // Gives the slot back if the initializer is dropped or panics.
+        let slot = Slot(self);
+        let item = init().await;
+        std::mem::forget(slot);
+        self.pooled(item)
+    }
+
+    /// Takes an item out of the pool if one is idle right away.
+    ///
+    /// Items are never created here, as the initializer is async.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::pool::Pool;
+    ///
+    /// let pool = Pool::from_items([1]);
+    ///
+    /// let item = pool.try_get().unwrap();
+    /// assert_eq!(*item, 1);
+    /// assert!(pool.try_get().is_none());
+    /// ```
+    pub fn try_get(&self) -> Option<Pooled<'_, T>> {
+        self.sema.try_acquire_exact(1).ok()?;
+        let Some(item) = self.items.lock().unwrap().idle.pop() else {
+            self.sema.release(1);
+            return None;
+        };
+        Some(self.pooled(item))
+    }
+
+    /// Adds an item to the pool, waking a task waiting for one.
+    ///
+    /// An item added to a lazy pool takes the place of one it has yet to
+    /// create. Otherwise the pool grows by one item, on top of any maximum
+    /// it was created with.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::pool::Pool;
+    ///
+    /// let pool = Pool::new(2, || async { 0 });
+    /// pool.add(1);
+    /// assert_eq!(pool.size(), 2);
+    ///
+    /// let item = pool.try_get().unwrap();
+    /// assert_eq!(*item, 1);
+    /// # drop(item);
+    /// ```
+    pub fn add(&self, item: T) {
+        let mut items = self.items.lock().unwrap();
+        items.idle.push(item);
+        if items.uncreated > 0 {
+            items.uncreated -= 1;
+            drop(items);
+            // The permit of the slot now stands for the idle item.
+            return;
+        }
+        drop(items);
+        self.sema.add_permits(1);
+    }
+
+    /// Returns the number of items that could be handed out without
+    /// waiting, idle or yet to be created.
+    pub fn available(&self) -> Permits {
+        self.sema.available_permits()
+    }
+
+    /// Returns the most items the pool hands out at once.
+    pub fn size(&self) -> Permits {
+        self.sema.capacity()
+    }
+
+    /// Takes an idle item with the permit for it acquired, or else reserves
+    /// the slot of an item to create.
+    fn take_idle(&self) -> Option<T> {
+        let mut items = self.items.lock().unwrap();
+        let item = items.idle.pop();
+        if item.is_none() {
+            assert!(
+                self.init.is_some(),
+                "a permit was acquired for an idle item"
+            );
+            // Without an idle item, the permit stands for one yet to be created.
+            items.uncreated -= 1;
+        }
+        item
+    }
+
+    fn pooled(&self, item: T) -> Pooled<'_, T> {
+        Pooled {
+            pool: self,
+            item: Some(item),
+        }
+    }
+}
+
+/// Gives back the slot of an item that failed to be created.
+struct Slot<'a, T>(&'a Pool<T>);
+
+impl<T> Drop for Slot<'_, T> {
+    fn drop(&mut self) {
+        self.0.items.lock().unwrap().uncreated += 1;
+        self.0.sema.release(1);
+    }
+}
+
+impl<T> FromIterator<T> for Pool<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Pool<T> {
+        Pool::from_items(items)
+    }
+}
+
+impl<T> fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("available", &self.available())
+            .field("size", &self.size())
+            .field("lazy", &self.init.is_some())
+            .finish()
+    }
+}
+
+/// An item taken out of a [`Pool`].
+///
+/// Dereferences to the item, and returns it to the pool when dropped.
+#[must_use = "the item returns to the pool as soon as the guard is dropped"]
+pub struct Pooled<'a, T> {
+    pool: &'a Pool<T>,
+    item: Option<T>,
+}
+
+impl<T> Pooled<'_, T> {
+    /// Takes the item out of the pool for good.
+    ///
+    /// A pool with an initializer creates a new item in its place when
+    /// needed, and other pools shrink by one item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::pool::Pool;
+    ///
+    /// let pool = Pool::from_items([1, 2]);
+    ///
+    /// let item = pool.try_get().unwrap().detach();
+    /// assert_eq!(pool.size(), 1);
+    /// # let _ = item;
+    /// ```
+    pub fn detach(mut self) -> T {
+        let item = self.item.take().unwrap();
+        if self.pool.init.is_some() {
+            self.pool.items.lock().unwrap().uncreated += 1;
+            self.pool.sema.release(1);
+        } else {
+            self.pool.sema.forget_permits(1);
+        }
+        item
+    }
+}
+
+impl<T> Deref for Pooled<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl<T> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.items.lock().unwrap().idle.push(item);
+            self.pool.sema.release(1);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Pooled<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Pooled").field(&**self).finish()
+    }
+}
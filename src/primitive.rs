@@ -0,0 +1,21 @@
+//! Synchronization primitives used by the semaphore core.
+//!
+//! Building with `--cfg loom` swaps them for loom's model-checked versions.
+
+#[cfg(all(loom, feature = "u64"))]
+pub(crate) use loom::sync::atomic::AtomicU64 as AtomicPermits;
+#[cfg(all(loom, not(feature = "u64")))]
+pub(crate) use loom::sync::atomic::AtomicUsize as AtomicPermits;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::sync::Mutex;
+
+#[cfg(all(not(loom), feature = "u64"))]
+pub(crate) use std::sync::atomic::AtomicU64 as AtomicPermits;
+#[cfg(all(not(loom), not(feature = "u64")))]
+pub(crate) use std::sync::atomic::AtomicUsize as AtomicPermits;
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+#[cfg(not(loom))]
+pub(crate) use std::sync::Mutex;
@@ -0,0 +1,113 @@
+use crate::grant_log::WaiterId;
+use crate::primitive::Mutex;
+use crate::{Permits, WakeOrder};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::task::Waker;
+use std::time::Instant;
+
+/// Progress of a queued acquire.
+#[derive(Debug)]
+pub(crate) enum NodeState {
+    /// Still queued, holding the waker of the task to notify.
+    Waiting(Option<Waker>),
+    /// The semaphore handed over this many permits.
+    Granted(Permits),
+    /// The semaphore was closed before permits could be granted.
+    Closed,
+}
+
+/// A task waiting for between `min` and `max` permits.
+#[derive(Debug)]
+pub(crate) struct Node {
+    pub min: Permits,
+    pub max: Permits,
+    pub priority: u8,
+    pub waiter: WaiterId,
+    pub queued_at: Instant,
+    pub state: Mutex<NodeState>,
+}
+
+impl Node {
+    pub fn new(
+        min: Permits,
+        max: Permits,
+        priority: u8,
+        waiter: WaiterId,
+        waker: &Waker,
+    ) -> Arc<Node> {
+        Arc::new(Node {
+            min,
+            max,
+            priority,
+            waiter,
+            queued_at: crate::time::now(),
+            state: Mutex::new(NodeState::Waiting(Some(waker.clone()))),
+        })
+    }
+
+    /// Moves the node out of the waiting state.
+    ///
+    /// Returns the waker to notify, which should be woken once the queue lock
+    /// has been released.
+    pub fn complete(&self, state: NodeState) -> Option<Waker> {
+        match std::mem::replace(&mut *self.state.lock().unwrap(), state) {
+            NodeState::Waiting(waker) => waker,
+            _ => None,
+        }
+    }
+}
+
+/// Tasks waiting for permits, in the order they are served.
+///
+/// Nodes are kept sorted from the highest priority to the lowest.
+#[derive(Debug)]
+pub(crate) struct WaitQueue {
+    nodes: VecDeque<Arc<Node>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> WaitQueue {
+        WaitQueue {
+            nodes: VecDeque::new(),
+        }
+    }
+
+    /// Returns where a new node with `priority` is queued according to `order`.
+    pub fn position(&self, priority: u8, order: WakeOrder) -> usize {
+        match order {
+            WakeOrder::Fifo => self.nodes.partition_point(|n| n.priority >= priority),
+            WakeOrder::Lifo => self.nodes.partition_point(|n| n.priority > priority),
+        }
+    }
+
+    pub fn insert(&mut self, pos: usize, node: Arc<Node>) {
+        self.nodes.insert(pos, node);
+    }
+
+    /// Removes `node` from the queue, returning whether it was still queued.
+    pub fn remove(&mut self, node: &Arc<Node>) -> bool {
+        match self.nodes.iter().position(|n| Arc::ptr_eq(n, node)) {
+            Some(i) => self.nodes.remove(i).is_some(),
+            None => false,
+        }
+    }
+
+    /// Keeps only the nodes for which `f` returns `true`, visiting them in order.
+    pub fn retain(&mut self, f: impl FnMut(&Arc<Node>) -> bool) {
+        self.nodes.retain(f);
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = Arc<Node>> + '_ {
+        self.nodes.drain(..)
+    }
+}
+
+/// A task polling the semaphore without a queued node.
+#[derive(Debug)]
+pub(crate) struct Poller {
+    pub waker: Waker,
+    /// The permits the task needs before polling again makes sense, or zero
+    /// if it waits for any change of the balance.
+    pub demand: Permits,
+}
@@ -0,0 +1,142 @@
+use crate::primitive::{AtomicBool, Mutex, Ordering};
+use crate::Permits;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+
+/// The streams subscribed to the releases of a semaphore.
+#[derive(Debug)]
+pub(crate) struct Subscribers {
+    /// Whether any stream is subscribed, so releases only take the lock
+    /// when someone listens.
+    any: AtomicBool,
+    logs: Mutex<Vec<Weak<Log>>>,
+}
+
+/// The releases a stream has yet to yield, and the task to wake for them.
+#[derive(Debug)]
+struct Log {
+    queue: Mutex<Queue>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    released: VecDeque<Permits>,
+    waker: Option<Waker>,
+    /// Set once the semaphore is gone and no more releases can come.
+    ended: bool,
+}
+
+impl Subscribers {
+    pub fn new() -> Self {
+        Self {
+            any: AtomicBool::new(false),
+            logs: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self) -> Releases {
+        let log = Arc::new(Log {
+            queue: Mutex::new(Queue::default()),
+        });
+        let mut logs = self.logs.lock().unwrap();
+        logs.push(Arc::downgrade(&log));
+        self.any.store(true, Ordering::Release);
+        Releases { log }
+    }
+
+    /// Hands a release of `n` permits to every stream.
+    pub fn record(&self, n: Permits) {
+        if !self.any.load(Ordering::Acquire) {
+            return;
+        }
+        let mut wakers = Vec::new();
+        let mut logs = self.logs.lock().unwrap();
+        logs.retain(|log| {
+            let Some(log) = log.upgrade() else {
+                return false;
+            };
+            let mut queue = log.queue.lock().unwrap();
+            queue.released.push_back(n);
+            wakers.extend(queue.waker.take());
+            true
+        });
+        self.any.store(!logs.is_empty(), Ordering::Release);
+        drop(logs);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl Drop for Subscribers {
+    fn drop(&mut self) {
+        for log in std::mem::take(&mut *self.logs.lock().unwrap()) {
+            if let Some(log) = log.upgrade() {
+                let mut queue = log.queue.lock().unwrap();
+                queue.ended = true;
+                if let Some(waker) = queue.waker.take() {
+                    drop(queue);
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+/// Stream returned by [`Semaphore::releases`].
+///
+/// Yields the number of permits of every release, whether a guard was
+/// dropped or permits were added, in the order they happened. Unlike a
+/// [`Watch`], no release is skipped: releases are kept until the stream
+/// takes them, so a stream that is not polled grows without bound. The
+/// stream ends once the semaphore is gone, along with any permits held from
+/// it.
+///
+/// Permits released right back to the semaphore are yielded too, such as
+/// those of an all-or-nothing acquire that found only some of the permits it
+/// needed.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+/// use futures::StreamExt;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(2);
+/// let mut releases = s.releases();
+///
+/// drop(s.acquire_many(2).await.unwrap());
+/// s.add_permits(3);
+/// assert_eq!(releases.next().await, Some(2));
+/// assert_eq!(releases.next().await, Some(3));
+///
+/// drop(s);
+/// assert_eq!(releases.next().await, None);
+/// # });
+/// ```
+///
+/// [`Semaphore::releases`]: crate::Semaphore::releases
+/// [`Watch`]: crate::Watch
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct Releases {
+    log: Arc<Log>,
+}
+
+impl Stream for Releases {
+    type Item = Permits;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Permits>> {
+        let mut queue = self.log.queue.lock().unwrap();
+        if let Some(n) = queue.released.pop_front() {
+            return Poll::Ready(Some(n));
+        }
+        if queue.ended {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
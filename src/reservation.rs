@@ -0,0 +1,71 @@
+use crate::deadlock::Hold;
+use crate::{Permits, SemaphoreInner, SemaphorePermit};
+
+/// Permits set aside by [`Semaphore::reserve`] while work is being prepared.
+///
+/// A reservation holds its permits like a guard, but must be turned into a
+/// [`SemaphorePermit`] with [`commit`] before the work starts. Dropping it
+/// without committing rolls the reservation back, so a failed setup never
+/// keeps capacity by accident.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(4);
+///
+/// let reservation = s.reserve(3).await.unwrap();
+/// assert_eq!(s.available_permits(), 1);
+///
+/// let setup: Result<(), &str> = Err("setup failed");
+/// if setup.is_err() {
+///     reservation.cancel();
+/// }
+/// assert_eq!(s.available_permits(), 4);
+///
+/// let permit = s.reserve(2).await.unwrap().commit();
+/// assert_eq!(permit.num_permits(), 2);
+/// # });
+/// ```
+///
+/// [`Semaphore::reserve`]: crate::Semaphore::reserve
+/// [`commit`]: Reservation::commit
+#[must_use = "the reservation is rolled back as soon as it is dropped"]
+#[derive(Debug)]
+pub struct Reservation<'a> {
+    inner: &'a SemaphoreInner,
+    permits: Permits,
+}
+
+impl<'a> Reservation<'a> {
+    pub(crate) fn new(inner: &'a SemaphoreInner, permits: Permits) -> Self {
+        Self { inner, permits }
+    }
+
+    /// Returns the number of reserved permits.
+    pub fn num_permits(&self) -> Permits {
+        self.permits
+    }
+
+    /// Confirms the reservation, handing its permits over to a guard.
+    pub fn commit(mut self) -> SemaphorePermit<'a> {
+        SemaphorePermit {
+            inner: self.inner,
+            permits: std::mem::take(&mut self.permits),
+            hold: Hold::none(),
+        }
+    }
+
+    /// Rolls the reservation back, returning its permits to the semaphore.
+    pub fn cancel(self) {}
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.inner.release(self.permits);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Support for deterministic simulation runtimes, such as madsim or tokio's
+//! paused time.
+//!
+//! By default, timeouts, leases and the starvation guard read the system
+//! clock and are woken by a background timer thread. Both break under a
+//! simulated runtime, where time only advances when the simulation says so
+//! and spawning OS threads is not allowed. Installing a [`Clock`] routes all
+//! of them through the runtime instead. With the `tokio` feature, tokio's
+//! clock and timer are used within a tokio runtime already, and an installed
+//! clock takes precedence over them.
+//!
+//! The `blocking_*` methods park the calling OS thread and cannot be made
+//! to work under simulation; use the async methods there.
+//!
+//! # Examples
+//!
+//! A clock backed by tokio's timer, which follows paused time in tests:
+//!
+//! ```
+//! use async_sema::sim::{set_clock, Clock};
+//! use std::task::Waker;
+//! use std::time::Instant;
+//!
+//! struct TokioClock;
+//!
+//! impl Clock for TokioClock {
+//!     fn now(&self) -> Instant {
+//!         tokio::time::Instant::now().into_std()
+//!     }
+//!
+//!     fn wake_at(&self, deadline: Instant, waker: Waker) {
+//!         tokio::spawn(async move {
+//!             tokio::time::sleep_until(deadline.into()).await;
+//!             waker.wake();
+//!         });
+//!     }
+//! }
+//!
+//! assert!(set_clock(TokioClock).is_ok());
+//! assert!(set_clock(TokioClock).is_err());
+//! ```
+
+use std::sync::OnceLock;
+use std::task::Waker;
+use std::time::Instant;
+
+/// A source of time and timer wakeups provided by the runtime.
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Wakes `waker` once `deadline` has passed.
+    ///
+    /// Waking late is fine, and so is waking early, as the deadline is
+    /// checked again.
+    fn wake_at(&self, deadline: Instant, waker: Waker);
+}
+
+static CLOCK: OnceLock<Box<dyn Clock>> = OnceLock::new();
+
+/// Installs the clock used by all semaphores in the process.
+///
+/// The clock can only be installed once and should be set before any timed
+/// operation starts. Returns the clock back if one was already installed.
+pub fn set_clock<C: Clock>(clock: C) -> Result<(), C> {
+    let mut clock = Some(clock);
+    CLOCK.get_or_init(|| Box::new(clock.take().unwrap()));
+    match clock {
+        Some(clock) => Err(clock),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn clock() -> Option<&'static dyn Clock> {
+    CLOCK.get().map(|clock| &**clock)
+}
@@ -0,0 +1,188 @@
+//! Sink adapters that bound writes in flight with a semaphore.
+//!
+//! The adapter of [`SinkExt`] takes a permit in `poll_ready` for each item
+//! and holds it until the item has been flushed, so a sink that buffers
+//! writes can only run as far ahead of its flushes as the semaphore allows.
+//! The semaphore may be shared by many sinks and tasks, all drawing on the
+//! same limit.
+
+use crate::{Acquire, AcquireError, Permits, Semaphore, SemaphorePermit};
+use futures_sink::Sink;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Extension methods for bounding the items written to a sink.
+///
+/// Implemented for every sink.
+pub trait SinkExt<Item>: Sink<Item> + Sized {
+    /// Holds a permit of `sema` for each item sent to this sink until the
+    /// sink has flushed it.
+    ///
+    /// `poll_ready` waits for a permit before it asks the sink whether it is
+    /// ready, and a flush or close that completes releases the permits of
+    /// every item sent before it. While waiting for a permit, the adapter
+    /// flushes the items it holds permits for, so a sink holding all the
+    /// permits of its semaphore still makes progress. If the semaphore is
+    /// closed, `poll_ready` fails with [`SinkError::Acquire`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, SinkExt as _};
+    /// use futures::{channel::mpsc, SinkExt, StreamExt};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(4);
+    /// let (tx, rx) = mpsc::unbounded::<u32>();
+    /// let mut tx = tx.limit_unflushed(&s);
+    ///
+    /// tx.feed(1).await.unwrap();
+    /// tx.feed(2).await.unwrap();
+    /// assert_eq!(s.available_permits(), 2);
+    ///
+    /// tx.flush().await.unwrap();
+    /// assert_eq!(s.available_permits(), 4);
+    ///
+    /// drop(tx);
+    /// assert_eq!(rx.collect::<Vec<_>>().await, [1, 2]);
+    /// # });
+    /// ```
+    fn limit_unflushed(self, sema: &Semaphore) -> LimitUnflushed<'_, Self> {
+        LimitUnflushed {
+            sink: Box::pin(self),
+            sema,
+            acquire: None,
+            ready: None,
+            unflushed: None,
+        }
+    }
+}
+
+impl<S: Sink<Item>, Item> SinkExt<Item> for S {}
+
+/// Error of a [`LimitUnflushed`] sink.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkError<E> {
+    /// No permit could be acquired for the next item.
+    Acquire(AcquireError),
+    /// The inner sink failed.
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for SinkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkError::Acquire(error) => error.fmt(f),
+            SinkError::Sink(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for SinkError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SinkError::Acquire(error) => Some(error),
+            SinkError::Sink(error) => Some(error),
+        }
+    }
+}
+
+/// Sink returned by [`SinkExt::limit_unflushed`].
+#[must_use = "sinks do nothing unless polled"]
+pub struct LimitUnflushed<'a, S> {
+    sink: Pin<Box<S>>,
+    sema: &'a Semaphore,
+    /// The acquire of the permit for the next item.
+    acquire: Option<Acquire<'a>>,
+    /// The permit taken for the next item, once the acquire completed.
+    ready: Option<SemaphorePermit<'a>>,
+    /// The permits of the items sent since the last flush.
+    unflushed: Option<SemaphorePermit<'a>>,
+}
+
+impl<S> LimitUnflushed<'_, S> {
+    /// Returns the inner sink.
+    pub fn get_ref(&self) -> &S {
+        &self.sink
+    }
+
+    /// Returns the number of items sent and not flushed yet.
+    pub fn unflushed(&self) -> Permits {
+        self.unflushed
+            .as_ref()
+            .map_or(0, SemaphorePermit::num_permits)
+    }
+
+    /// Flushes the inner sink, releasing the permits of every item sent.
+    fn poll_flush_held<Item>(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>>
+    where
+        S: Sink<Item>,
+    {
+        ready!(self.sink.as_mut().poll_flush(cx))?;
+        self.unflushed = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: Sink<Item>, Item> Sink<Item> for LimitUnflushed<'_, S> {
+    type Error = SinkError<S::Error>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        while this.ready.is_none() {
+            let sema = this.sema;
+            let acquire = this.acquire.get_or_insert_with(|| sema.acquire());
+            if let Poll::Ready(permit) = Pin::new(acquire).poll(cx) {
+                this.acquire = None;
+                this.ready = Some(permit.map_err(SinkError::Acquire)?.untracked());
+            } else if this.unflushed.is_none() {
+                return Poll::Pending;
+            } else {
+                // The permits may all be held by items this sink has yet to flush.
+                ready!(this.poll_flush_held(cx)).map_err(SinkError::Sink)?;
+            }
+        }
+        this.sink.as_mut().poll_ready(cx).map_err(SinkError::Sink)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let permit = this
+            .ready
+            .take()
+            .expect("`start_send` called without `poll_ready` returning `Ready(Ok(()))`");
+        this.sink
+            .as_mut()
+            .start_send(item)
+            .map_err(SinkError::Sink)?;
+        match &mut this.unflushed {
+            Some(unflushed) => unflushed.merge(permit),
+            None => this.unflushed = Some(permit),
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_flush_held(cx).map_err(SinkError::Sink)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.sink.as_mut().poll_close(cx)).map_err(SinkError::Sink)?;
+        this.ready = None;
+        this.unflushed = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> fmt::Debug for LimitUnflushed<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LimitUnflushed")
+            .field("ready", &self.ready.is_some())
+            .field("unflushed", &self.unflushed())
+            .finish()
+    }
+}
@@ -0,0 +1,206 @@
+//! Spawning tokio tasks and blocking work that hold a permit, with the
+//! `tokio` feature.
+
+use crate::{AcquireError, OwnedSemaphorePermit, Permits, Semaphore};
+use std::fmt;
+use std::future::Future;
+use tokio::task::{AbortHandle, JoinError, JoinHandle, JoinSet};
+
+impl Semaphore {
+    /// Waits for a permit, then spawns `future` on the current tokio runtime
+    /// with the permit held until the task ends.
+    ///
+    /// The permit is released however the task ends, whether the future
+    /// completes, panics, or the task is aborted. Only the wait for the
+    /// permit happens in the caller, so once this returns, the task runs
+    /// without the caller polling it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// let mut tasks = Vec::new();
+    /// for n in 0..8 {
+    ///     tasks.push(s.spawn_limited(async move { n * 2 }).await.unwrap());
+    /// }
+    /// let mut total = 0;
+    /// for task in tasks {
+    ///     total += task.await.unwrap();
+    /// }
+    /// assert_eq!(total, 56);
+    /// assert_eq!(s.available_permits(), 2);
+    /// # });
+    /// ```
+    pub async fn spawn_limited<F>(&self, future: F) -> Result<JoinHandle<F::Output>, AcquireError>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let permit = self.acquire_for_task().await?;
+        Ok(tokio::spawn(holding(permit, future)))
+    }
+
+    /// Waits for a permit, then runs `f` on tokio's blocking thread pool with
+    /// the permit held until it returns.
+    ///
+    /// This bounds the blocking work in flight, which the blocking pool
+    /// itself only does at its much larger thread limit. The permit is
+    /// released once `f` returns or panics.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::Semaphore;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(1);
+    ///
+    /// let read = s.spawn_blocking_limited(|| "contents".len()).await.unwrap();
+    /// assert_eq!(read.await.unwrap(), 8);
+    /// assert_eq!(s.available_permits(), 1);
+    /// # });
+    /// ```
+    pub async fn spawn_blocking_limited<F, R>(&self, f: F) -> Result<JoinHandle<R>, AcquireError>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let permit = self.acquire_for_task().await?;
+        Ok(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            f()
+        }))
+    }
+
+    /// Waits for a permit to hand to a task about to be spawned.
+    async fn acquire_for_task(&self) -> Result<OwnedSemaphorePermit, AcquireError> {
+        Ok(self.acquire_owned().await?.untracked())
+    }
+}
+
+/// Runs `future` with `permit` held until it completes or is dropped.
+async fn holding<F: Future>(permit: OwnedSemaphorePermit, future: F) -> F::Output {
+    let _permit = permit;
+    future.await
+}
+
+/// A [`JoinSet`] running at most as many tasks at once as its semaphore
+/// has permits.
+///
+/// [`spawn`](LimitedJoinSet::spawn) waits for a permit before it spawns the
+/// task, so a loop spawning more tasks than the limit is held back until
+/// running ones finish. Each task gives its permit back as soon as it ends,
+/// whether or not it has been joined yet, so a set that is only joined once
+/// everything has been spawned still makes progress.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::LimitedJoinSet;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let mut set = LimitedJoinSet::new(2);
+///
+/// for n in 0..8 {
+///     set.spawn(async move { n * 2 }).await.unwrap();
+/// }
+/// let mut total = 0;
+/// while let Some(output) = set.join_next().await {
+///     total += output.unwrap();
+/// }
+/// assert_eq!(total, 56);
+/// # });
+/// ```
+pub struct LimitedJoinSet<T> {
+    sema: Semaphore,
+    tasks: JoinSet<T>,
+}
+
+impl<T: 'static> LimitedJoinSet<T> {
+    /// Creates a set running up to `limit` tasks at once.
+    pub fn new(limit: Permits) -> LimitedJoinSet<T> {
+        LimitedJoinSet::with_semaphore(Semaphore::new(limit))
+    }
+
+    /// Creates a set running each task under a permit of `sema`.
+    ///
+    /// The semaphore may be shared with other sets and tasks, all drawing on
+    /// the same limit.
+    pub fn with_semaphore(sema: Semaphore) -> LimitedJoinSet<T> {
+        LimitedJoinSet {
+            sema,
+            tasks: JoinSet::new(),
+        }
+    }
+
+    /// Waits for a permit, then spawns `future` into the set on the current
+    /// tokio runtime.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub async fn spawn<F>(&mut self, future: F) -> Result<AbortHandle, AcquireError>
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send,
+    {
+        let permit = self.sema.acquire_for_task().await?;
+        Ok(self.tasks.spawn(holding(permit, future)))
+    }
+
+    /// Waits for the next task to end, and returns its output.
+    ///
+    /// Returns `None` once the set is empty.
+    pub async fn join_next(&mut self) -> Option<Result<T, JoinError>> {
+        self.tasks.join_next().await
+    }
+
+    /// Aborts every task in the set.
+    ///
+    /// The tasks stay in the set until they are joined.
+    pub fn abort_all(&mut self) {
+        self.tasks.abort_all();
+    }
+
+    /// Aborts every task in the set, and waits for all of them to end.
+    pub async fn shutdown(&mut self) {
+        self.tasks.shutdown().await;
+    }
+
+    /// Returns the number of tasks in the set, including those that have
+    /// ended and are not joined yet.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Returns whether the set has no tasks.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Returns the semaphore limiting the running tasks.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+}
+
+impl<T> fmt::Debug for LimitedJoinSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LimitedJoinSet")
+            .field("tasks", &self.tasks.len())
+            .field("available", &self.sema.available_permits())
+            .finish()
+    }
+}
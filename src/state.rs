@@ -0,0 +1,22 @@
+use crate::Permits;
+
+/// A snapshot of a semaphore's state, returned by [`Semaphore::state`].
+///
+/// The values are read together while holding the wait queue lock, so the
+/// waiters and the closed flag agree with each other. Permits can still be
+/// acquired and released concurrently, so the counts are meant for
+/// diagnostics rather than synchronization.
+///
+/// [`Semaphore::state`]: crate::Semaphore::state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SemaphoreState {
+    /// The number of permits currently available.
+    pub available: Permits,
+    /// The total number of permits managed by the semaphore.
+    pub capacity: Permits,
+    /// The number of tasks waiting for permits.
+    pub waiters: usize,
+    /// Whether the semaphore has been closed.
+    pub closed: bool,
+}
@@ -0,0 +1,45 @@
+use crate::{Permits, Semaphore};
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+/// A [`Semaphore`] that can be declared in a `static`.
+///
+/// The underlying semaphore is created on first use and all of its methods
+/// are available through `Deref`.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::StaticSemaphore;
+///
+/// static LIMIT: StaticSemaphore = StaticSemaphore::new(2);
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let guard = LIMIT.acquire().await.unwrap();
+/// assert_eq!(LIMIT.available_permits(), 1);
+/// # drop(guard);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct StaticSemaphore {
+    permits: Permits,
+    sema: OnceLock<Semaphore>,
+}
+
+impl StaticSemaphore {
+    /// Creates a new semaphore with a limit of `n` concurrent operations.
+    pub const fn new(n: Permits) -> StaticSemaphore {
+        StaticSemaphore {
+            permits: n,
+            sema: OnceLock::new(),
+        }
+    }
+}
+
+impl Deref for StaticSemaphore {
+    type Target = Semaphore;
+
+    fn deref(&self) -> &Semaphore {
+        self.sema.get_or_init(|| Semaphore::new(self.permits))
+    }
+}
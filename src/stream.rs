@@ -0,0 +1,509 @@
+//! Stream adapters that run work under a semaphore.
+//!
+//! The adapters of [`StreamExt`] pull an item from the source stream only
+//! once a permit is free for it, so a slow consumer or a full semaphore
+//! pushes back on the source instead of buffering its items. The semaphore
+//! may be shared by many streams and tasks, all drawing on the same limit.
+//!
+//! [`LimitedFuturesUnordered`] does the same for a set of futures pushed one
+//! at a time rather than taken from a stream.
+
+use crate::unordered::Unordered;
+use crate::{Acquire, AcquireError, Semaphore, SemaphorePermit};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Extension methods for running the items of a stream under a semaphore.
+///
+/// Implemented for every stream.
+pub trait StreamExt: Stream + Sized {
+    /// Runs `f` on each item, with as many running at once as `sema` has
+    /// permits, and yields their outputs in the order they finish.
+    ///
+    /// Each future holds a permit until it finishes, and the next item is
+    /// only taken from the stream once a permit is free for it. If the
+    /// semaphore is closed, the error is yielded once and the stream ends
+    /// after the futures that are still running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, StreamExt as _};
+    /// use futures::{stream, StreamExt};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// let mut doubled: Vec<_> = stream::iter(1..=4)
+    ///     .map_concurrent_limited(&s, |n| async move { n * 2 })
+    ///     .map(Result::unwrap)
+    ///     .collect()
+    ///     .await;
+    /// doubled.sort();
+    /// assert_eq!(doubled, [2, 4, 6, 8]);
+    /// # });
+    /// ```
+    fn map_concurrent_limited<F, Fut>(
+        self,
+        sema: &Semaphore,
+        f: F,
+    ) -> MapConcurrentLimited<'_, Self, F, Fut>
+    where
+        F: FnMut(Self::Item) -> Fut,
+        Fut: Future,
+    {
+        MapConcurrentLimited {
+            stream: Some(Box::pin(self)),
+            sema,
+            f,
+            next: None,
+            running: Unordered::new(),
+        }
+    }
+
+    /// Runs `f` on each item, with as many running at once as `sema` has
+    /// permits, and completes once all of them have finished.
+    ///
+    /// Unlike a limit passed to each call, the semaphore may be shared by
+    /// any number of streams, which then take turns under one global limit.
+    /// If the semaphore is closed, no more items are started, and the error
+    /// is returned once the running ones have finished.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, StreamExt};
+    /// use futures::stream;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(3);
+    /// let total = AtomicUsize::new(0);
+    /// let add = |n| {
+    ///     let total = &total;
+    ///     async move {
+    ///         total.fetch_add(n, Ordering::Relaxed);
+    ///     }
+    /// };
+    ///
+    /// // Both consumers share the three permits.
+    /// let (a, b) = futures::join!(
+    ///     stream::iter(0..10).for_each_concurrent_limited(&s, add),
+    ///     stream::iter(10..20).for_each_concurrent_limited(&s, add),
+    /// );
+    /// assert_eq!((a, b), (Ok(()), Ok(())));
+    /// assert_eq!(total.load(Ordering::Relaxed), 190);
+    /// # });
+    /// ```
+    fn for_each_concurrent_limited<F, Fut>(
+        self,
+        sema: &Semaphore,
+        f: F,
+    ) -> ForEachConcurrentLimited<'_, Self, F, Fut>
+    where
+        F: FnMut(Self::Item) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        ForEachConcurrentLimited {
+            stream: self.map_concurrent_limited(sema, f),
+            error: None,
+        }
+    }
+
+    /// Runs the futures of this stream, with as many running at once as
+    /// `sema` has permits, and yields their outputs in the order they finish.
+    ///
+    /// Works like `buffer_unordered`, with the number of futures in flight
+    /// drawn from a semaphore that other streams may share, rather than a
+    /// limit of its own. A future is taken from the stream and first polled
+    /// once a permit is free for it. See [`map_concurrent_limited`] for what
+    /// happens when the semaphore is closed.
+    ///
+    /// [`map_concurrent_limited`]: StreamExt::map_concurrent_limited
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, StreamExt as _};
+    /// use futures::{stream, StreamExt};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let s = Semaphore::new(2);
+    ///
+    /// let pages = stream::iter(1..=3).map(|page| async move { format!("page {page}") });
+    /// let mut fetched: Vec<_> = pages
+    ///     .buffered_limited(&s)
+    ///     .map(Result::unwrap)
+    ///     .collect()
+    ///     .await;
+    /// fetched.sort();
+    /// assert_eq!(fetched, ["page 1", "page 2", "page 3"]);
+    /// # });
+    /// ```
+    fn buffered_limited(self, sema: &Semaphore) -> BufferedLimited<'_, Self>
+    where
+        Self::Item: Future,
+    {
+        BufferedLimited {
+            stream: self.map_concurrent_limited(sema, |future| future),
+        }
+    }
+
+    /// Yields the items of this stream one permit of `sema` at a time, each
+    /// item using up its permit.
+    ///
+    /// This is credit-based flow control: the consumer grants credit by
+    /// adding permits, and each item taken from the source waits for a
+    /// permit before it is yielded. The stream ends as soon as the source
+    /// does, without waiting for credit. If the semaphore is closed, the
+    /// error is yielded once and the stream ends.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::{Semaphore, StreamExt as _};
+    /// use futures::{stream, StreamExt};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let credit = Semaphore::new(2);
+    /// let mut items = stream::iter(1..=3).throttle_by(&credit);
+    ///
+    /// assert_eq!(items.next().await, Some(Ok(1)));
+    /// assert_eq!(items.next().await, Some(Ok(2)));
+    /// assert!(futures::poll!(items.next()).is_pending());
+    ///
+    /// // The consumer has room for one more item.
+    /// credit.add_permits(1);
+    /// assert_eq!(items.next().await, Some(Ok(3)));
+    /// assert_eq!(items.next().await, None);
+    /// # });
+    /// ```
+    fn throttle_by(self, sema: &Semaphore) -> ThrottleBy<'_, Self> {
+        ThrottleBy {
+            stream: Some(Box::pin(self)),
+            sema,
+            next: None,
+        }
+    }
+}
+
+impl<S: Stream> StreamExt for S {}
+
+/// Stream returned by [`StreamExt::map_concurrent_limited`].
+#[must_use = "streams do nothing unless polled"]
+pub struct MapConcurrentLimited<'a, S: Stream, F, Fut: Future> {
+    /// The source, until it ends or the semaphore fails.
+    stream: Option<Pin<Box<S>>>,
+    sema: &'a Semaphore,
+    f: F,
+    /// An item taken from the source, waiting for its permit.
+    next: Option<(S::Item, Acquire<'a>)>,
+    running: Unordered<Fut, SemaphorePermit<'a>>,
+}
+
+impl<'a, S, F, Fut> MapConcurrentLimited<'a, S, F, Fut>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future,
+{
+    /// Starts items from the source for as long as permits are free.
+    fn poll_start(&mut self, cx: &mut Context<'_>) -> Poll<AcquireError> {
+        while let Some(stream) = &mut self.stream {
+            if self.next.is_none() {
+                match stream.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(item)) => self.next = Some((item, self.sema.acquire())),
+                    Poll::Ready(None) => self.stream = None,
+                    Poll::Pending => break,
+                }
+                continue;
+            }
+
+            let (_, acquire) = self.next.as_mut().unwrap();
+            let acquired = ready!(Pin::new(acquire).poll(cx));
+            let (item, _) = self.next.take().unwrap();
+            match acquired {
+                Ok(permit) => self.running.push((self.f)(item), permit.untracked()),
+                Err(err) => {
+                    self.stream = None;
+                    return Poll::Ready(err);
+                }
+            }
+        }
+        Poll::Pending
+    }
+}
+
+// The source stream and the running futures are boxed, and the other fields
+// are never pinned.
+impl<S: Stream, F, Fut: Future> Unpin for MapConcurrentLimited<'_, S, F, Fut> {}
+
+impl<S, F, Fut> Stream for MapConcurrentLimited<'_, S, F, Fut>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future,
+{
+    type Item = Result<Fut::Output, AcquireError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Poll::Ready(err) = this.poll_start(cx) {
+            return Poll::Ready(Some(Err(err)));
+        }
+        match this.running.poll_next(cx) {
+            Poll::Ready(Some((output, _permit))) => Poll::Ready(Some(Ok(output))),
+            Poll::Ready(None) if this.stream.is_none() => Poll::Ready(None),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl<S: Stream, F, Fut: Future> fmt::Debug for MapConcurrentLimited<'_, S, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapConcurrentLimited")
+            .field("running", &self.running.len())
+            .field("done", &self.stream.is_none())
+            .finish()
+    }
+}
+
+/// Future returned by [`StreamExt::for_each_concurrent_limited`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct ForEachConcurrentLimited<'a, S: Stream, F, Fut: Future> {
+    stream: MapConcurrentLimited<'a, S, F, Fut>,
+    error: Option<AcquireError>,
+}
+
+impl<S, F, Fut> Future for ForEachConcurrentLimited<'_, S, F, Fut>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    type Output = Result<(), AcquireError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while let Some(done) = ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+            if let Err(err) = done {
+                this.error = Some(err);
+            }
+        }
+        Poll::Ready(this.error.take().map_or(Ok(()), Err))
+    }
+}
+
+impl<S: Stream, F, Fut: Future> fmt::Debug for ForEachConcurrentLimited<'_, S, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForEachConcurrentLimited")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+/// Stream returned by [`StreamExt::buffered_limited`].
+#[must_use = "streams do nothing unless polled"]
+pub struct BufferedLimited<'a, S: Stream>
+where
+    S::Item: Future,
+{
+    stream: MapConcurrentLimited<'a, S, Identity<S::Item>, S::Item>,
+}
+
+type Identity<T> = fn(T) -> T;
+
+impl<S: Stream> Stream for BufferedLimited<'_, S>
+where
+    S::Item: Future,
+{
+    type Item = Result<<S::Item as Future>::Output, AcquireError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream).poll_next(cx)
+    }
+}
+
+impl<S: Stream> fmt::Debug for BufferedLimited<'_, S>
+where
+    S::Item: Future,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferedLimited")
+            .field("stream", &self.stream)
+            .finish()
+    }
+}
+
+/// Stream returned by [`StreamExt::throttle_by`].
+#[must_use = "streams do nothing unless polled"]
+pub struct ThrottleBy<'a, S: Stream> {
+    /// The source, until it ends or the semaphore fails.
+    stream: Option<Pin<Box<S>>>,
+    sema: &'a Semaphore,
+    /// An item taken from the source, waiting for its credit.
+    next: Option<(S::Item, Acquire<'a>)>,
+}
+
+// The source stream is boxed, and the other fields are never pinned.
+impl<S: Stream> Unpin for ThrottleBy<'_, S> {}
+
+impl<S: Stream> Stream for ThrottleBy<'_, S> {
+    type Item = Result<S::Item, AcquireError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(stream) = &mut this.stream else {
+            return Poll::Ready(None);
+        };
+        if this.next.is_none() {
+            let Some(item) = ready!(stream.as_mut().poll_next(cx)) else {
+                this.stream = None;
+                return Poll::Ready(None);
+            };
+            this.next = Some((item, this.sema.acquire()));
+        }
+
+        let (_, acquire) = this.next.as_mut().unwrap();
+        let acquired = ready!(Pin::new(acquire).poll(cx));
+        let (item, _) = this.next.take().unwrap();
+        match acquired {
+            Ok(permit) => {
+                permit.forget();
+                Poll::Ready(Some(Ok(item)))
+            }
+            Err(err) => {
+                this.stream = None;
+                Poll::Ready(Some(Err(err)))
+            }
+        }
+    }
+}
+
+impl<S: Stream> fmt::Debug for ThrottleBy<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThrottleBy")
+            .field("waiting", &self.next.is_some())
+            .field("done", &self.stream.is_none())
+            .finish()
+    }
+}
+
+/// A set of futures each running under a permit of a semaphore, yielding
+/// their outputs in the order they finish.
+///
+/// Works like `FuturesUnordered`, with admission bounded by the semaphore:
+/// [`push`] waits for a permit before it adds a future to the set, and the
+/// permit is released as soon as the future finishes. While `push` waits,
+/// it polls the futures already in the set and keeps the outputs of those
+/// that finish for the stream, so a task filling the set up to its limit
+/// before draining it still makes progress.
+///
+/// Like `FuturesUnordered`, the stream ends whenever the set is empty, and
+/// yields again once more futures are pushed.
+///
+/// [`push`]: LimitedFuturesUnordered::push
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::stream::LimitedFuturesUnordered;
+/// use async_sema::Semaphore;
+/// use futures::StreamExt;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(2);
+/// let mut set = LimitedFuturesUnordered::new(&s);
+///
+/// for n in 1..=4 {
+///     set.push(async move { n * 2 }).await.unwrap();
+/// }
+/// assert!(set.len() <= 4);
+///
+/// let mut doubled: Vec<_> = set.collect().await;
+/// doubled.sort();
+/// assert_eq!(doubled, [2, 4, 6, 8]);
+/// assert_eq!(s.available_permits(), 2);
+/// # });
+/// ```
+#[must_use = "streams do nothing unless polled"]
+pub struct LimitedFuturesUnordered<'a, Fut: Future> {
+    sema: &'a Semaphore,
+    running: Unordered<Fut, SemaphorePermit<'a>>,
+    /// Outputs of futures that finished while `push` waited for a permit.
+    done: VecDeque<Fut::Output>,
+}
+
+impl<'a, Fut: Future> LimitedFuturesUnordered<'a, Fut> {
+    /// Creates an empty set running its futures under permits of `sema`.
+    pub fn new(sema: &'a Semaphore) -> Self {
+        Self {
+            sema,
+            running: Unordered::new(),
+            done: VecDeque::new(),
+        }
+    }
+
+    /// Waits for a permit, then adds `future` to the set.
+    ///
+    /// The future is first polled by the next poll of the stream. If the
+    /// semaphore is closed, the future is dropped and the error returned.
+    pub async fn push(&mut self, future: Fut) -> Result<(), AcquireError> {
+        let mut acquire = self.sema.acquire();
+        let permit = poll_fn(|cx| loop {
+            if let Poll::Ready(acquired) = Pin::new(&mut acquire).poll(cx) {
+                return Poll::Ready(acquired);
+            }
+            // The permits may all be held by futures of this set, which
+            // only finish when polled.
+            match self.running.poll_next(cx) {
+                Poll::Ready(Some((output, _permit))) => self.done.push_back(output),
+                _ => return Poll::Pending,
+            }
+        })
+        .await?;
+        self.running.push(future, permit.untracked());
+        Ok(())
+    }
+
+    /// Returns the number of futures in the set, counting those that
+    /// finished and whose output is yet to be yielded.
+    pub fn len(&self) -> usize {
+        self.running.len() + self.done.len()
+    }
+
+    /// Returns whether the set has no futures.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// The futures are boxed, and the outputs are never pinned.
+impl<Fut: Future> Unpin for LimitedFuturesUnordered<'_, Fut> {}
+
+impl<Fut: Future> Stream for LimitedFuturesUnordered<'_, Fut> {
+    type Item = Fut::Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(output) = this.done.pop_front() {
+            return Poll::Ready(Some(output));
+        }
+        this.running
+            .poll_next(cx)
+            .map(|done| done.map(|(output, _permit)| output))
+    }
+}
+
+impl<Fut: Future> fmt::Debug for LimitedFuturesUnordered<'_, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LimitedFuturesUnordered")
+            .field("running", &self.running.len())
+            .field("done", &self.done.len())
+            .finish()
+    }
+}
@@ -0,0 +1,30 @@
+//! Async locks and coordination primitives.
+//!
+//! They reuse the waiting machinery behind [`Semaphore`], so a crate that
+//! needs both a concurrency limit and a lock gets both from one dependency.
+//!
+//! [`Semaphore`]: crate::Semaphore
+
+mod barrier;
+mod condvar;
+mod doorbell;
+mod gate;
+mod latch;
+mod mutex;
+mod notify;
+mod once;
+mod phaser;
+mod rwlock;
+mod wait_group;
+
+pub use barrier::{Barrier, BarrierWaitResult};
+pub use condvar::Condvar;
+pub use doorbell::Doorbell;
+pub use gate::Gate;
+pub use latch::CountdownLatch;
+pub use mutex::{Mutex, MutexGuard, OwnedMutexGuard};
+pub use notify::Notify;
+pub use once::AsyncOnce;
+pub use phaser::Phaser;
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use wait_group::{WaitGroup, WaitGroupGuard};
@@ -0,0 +1,103 @@
+use event_listener::Event;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A rendezvous point for a fixed number of tasks.
+///
+/// Each call to [`wait`] blocks until `n` tasks have called it, then all of
+/// them continue together. The barrier can be reused for the next phase right
+/// away.
+///
+/// [`wait`]: Barrier::wait
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Barrier;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let barrier = Arc::new(Barrier::new(4));
+///
+/// let mut tasks = Vec::new();
+/// for _ in 0..4 {
+///     let barrier = barrier.clone();
+///     tasks.push(tokio::spawn(async move {
+///         barrier.wait().await.is_leader()
+///     }));
+/// }
+///
+/// let mut leaders = 0;
+/// for task in tasks {
+///     leaders += task.await.unwrap() as usize;
+/// }
+/// assert_eq!(leaders, 1);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Barrier {
+    n: usize,
+    arrived: Mutex<usize>,
+    generation: AtomicUsize,
+    event: Event,
+}
+
+impl Barrier {
+    /// Creates a barrier that releases tasks in groups of `n`.
+    ///
+    /// A barrier for zero tasks behaves like one for a single task.
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            n: n.max(1),
+            arrived: Mutex::new(0),
+            generation: AtomicUsize::new(0),
+            event: Event::new(),
+        }
+    }
+
+    /// Waits until all `n` tasks of the current phase have arrived.
+    ///
+    /// Exactly one task of each phase, the last to arrive, is told it is the
+    /// leader. A task arrives when the future is first polled: one that
+    /// stops waiting early, by dropping the future once it has been polled,
+    /// still counts as having arrived, while a future dropped before it was
+    /// ever polled does not.
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let generation = {
+            let mut arrived = self.arrived.lock().unwrap();
+            *arrived += 1;
+            if *arrived < self.n {
+                self.generation.load(Ordering::Acquire)
+            } else {
+                *arrived = 0;
+                self.generation.fetch_add(1, Ordering::AcqRel);
+                drop(arrived);
+                self.event.notify(usize::MAX);
+                return BarrierWaitResult(true);
+            }
+        };
+
+        loop {
+            if self.generation.load(Ordering::Acquire) != generation {
+                return BarrierWaitResult(false);
+            }
+
+            let listener = self.event.listen();
+            if self.generation.load(Ordering::Acquire) != generation {
+                return BarrierWaitResult(false);
+            }
+            listener.await;
+        }
+    }
+}
+
+/// Returned by [`Barrier::wait`] once all tasks of a phase have arrived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns whether this task was the last to arrive, and so leads the phase.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
@@ -0,0 +1,74 @@
+use event_listener::Event;
+
+/// Waits for a condition on shared state to become true.
+///
+/// Unlike the condition variable of the standard library, `Condvar` is not
+/// tied to a lock. A task passes a predicate to [`wait_until`], which is
+/// checked again each time another task calls [`notify_all`] after changing
+/// the state the predicate reads.
+///
+/// [`wait_until`]: Condvar::wait_until
+/// [`notify_all`]: Condvar::notify_all
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Condvar;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let loaded = Arc::new(AtomicUsize::new(0));
+/// let cond = Arc::new(Condvar::new());
+///
+/// for _ in 0..3 {
+///     let (loaded, cond) = (loaded.clone(), cond.clone());
+///     tokio::spawn(async move {
+///         loaded.fetch_add(1, Ordering::SeqCst);
+///         cond.notify_all();
+///     });
+/// }
+///
+/// cond.wait_until(|| loaded.load(Ordering::SeqCst) == 3).await;
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Condvar {
+    event: Event,
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub fn new() -> Condvar {
+        Condvar::default()
+    }
+
+    /// Waits until `condition` returns `true`.
+    ///
+    /// The condition is checked right away, and again after every call to
+    /// [`notify_all`]. A change of the state that is not followed by a
+    /// notification may go unnoticed.
+    ///
+    /// [`notify_all`]: Condvar::notify_all
+    pub async fn wait_until<F>(&self, mut condition: F)
+    where
+        F: FnMut() -> bool,
+    {
+        loop {
+            if condition() {
+                return;
+            }
+
+            let listener = self.event.listen();
+            if condition() {
+                return;
+            }
+            listener.await;
+        }
+    }
+
+    /// Wakes every waiting task to check its condition again.
+    pub fn notify_all(&self) {
+        self.event.notify(usize::MAX);
+    }
+}
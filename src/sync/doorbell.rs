@@ -0,0 +1,90 @@
+use event_listener::Event;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A doorbell one task rings to wake another.
+///
+/// Also known as an auto-reset event: ringing it stores a single signal,
+/// which the next call to [`wait`] takes, so rings made while the consumer
+/// is busy collapse into one wakeup. It suits a producer kicking an event
+/// loop, where the loop checks all of its work each time it wakes, and is
+/// cheaper than a semaphore as it only counts to one.
+///
+/// [`wait`]: Doorbell::wait
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Doorbell;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let bell = Arc::new(Doorbell::new());
+///
+/// let consumer = tokio::spawn({
+///     let bell = bell.clone();
+///     async move { bell.wait().await }
+/// });
+///
+/// bell.ring();
+/// consumer.await.unwrap();
+///
+/// // Several rings before a wait only wake it once.
+/// bell.ring();
+/// bell.ring();
+/// bell.wait().await;
+/// assert!(!bell.is_rung());
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Doorbell {
+    rung: AtomicBool,
+    event: Event,
+}
+
+impl Doorbell {
+    /// Creates a doorbell that has not been rung.
+    pub fn new() -> Doorbell {
+        Doorbell::default()
+    }
+
+    /// Rings the doorbell, waking the waiting task or the next one to wait.
+    pub fn ring(&self) {
+        if !self.rung.swap(true, Ordering::AcqRel) {
+            self.event.notify(1);
+        }
+    }
+
+    /// Waits until the doorbell is rung, and takes the ring.
+    pub async fn wait(&self) {
+        loop {
+            if self.take() {
+                return;
+            }
+
+            let listener = self.event.listen();
+            if self.take() {
+                return;
+            }
+            listener.await;
+        }
+    }
+
+    /// Takes the ring if the doorbell has been rung, without waiting.
+    pub fn try_wait(&self) -> bool {
+        self.take()
+    }
+
+    /// Clears a ring no task has taken yet.
+    pub fn reset(&self) {
+        self.rung.store(false, Ordering::Release);
+    }
+
+    /// Returns whether the doorbell has been rung and not taken yet.
+    pub fn is_rung(&self) -> bool {
+        self.rung.load(Ordering::Acquire)
+    }
+
+    fn take(&self) -> bool {
+        self.rung.swap(false, Ordering::AcqRel)
+    }
+}
@@ -0,0 +1,82 @@
+use event_listener::Event;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A gate that tasks pass while it is open and wait at while it is closed.
+///
+/// Also known as a manual-reset event: opening the gate releases every
+/// waiting task, and it stays open until it is closed again.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Gate;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let gate = Arc::new(Gate::new(false));
+///
+/// let waiter = tokio::spawn({
+///     let gate = gate.clone();
+///     async move { gate.wait().await }
+/// });
+///
+/// gate.open();
+/// waiter.await.unwrap();
+///
+/// // The gate stays open until it is closed again.
+/// gate.wait().await;
+/// gate.close();
+/// assert!(!gate.is_open());
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Gate {
+    open: AtomicBool,
+    event: Event,
+}
+
+impl Gate {
+    /// Creates a gate that starts out open or closed.
+    pub fn new(open: bool) -> Gate {
+        Gate {
+            open: AtomicBool::new(open),
+            event: Event::new(),
+        }
+    }
+
+    /// Opens the gate, letting every waiting task through.
+    pub fn open(&self) {
+        self.open.store(true, Ordering::Release);
+        self.event.notify(usize::MAX);
+    }
+
+    /// Closes the gate, so tasks wait until it is opened again.
+    pub fn close(&self) {
+        self.open.store(false, Ordering::Release);
+    }
+
+    /// Returns whether the gate is open.
+    pub fn is_open(&self) -> bool {
+        self.open.load(Ordering::Acquire)
+    }
+
+    /// Waits until the gate is open.
+    ///
+    /// A task woken by [`open`] may find the gate closed again by the time it
+    /// runs, in which case it keeps waiting.
+    ///
+    /// [`open`]: Gate::open
+    pub async fn wait(&self) {
+        loop {
+            if self.is_open() {
+                return;
+            }
+
+            let listener = self.event.listen();
+            if self.is_open() {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
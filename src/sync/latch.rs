@@ -0,0 +1,82 @@
+use event_listener::Event;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A latch that opens once it has been counted down a number of times.
+///
+/// Unlike a [`Barrier`], the tasks counting down do not wait, and the latch
+/// stays open once the count has reached zero.
+///
+/// [`Barrier`]: crate::sync::Barrier
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::CountdownLatch;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let ready = Arc::new(CountdownLatch::new(3));
+///
+/// for _ in 0..3 {
+///     let ready = ready.clone();
+///     tokio::spawn(async move {
+///         // Start a subsystem, then report it ready.
+///         ready.count_down();
+///     });
+/// }
+///
+/// ready.wait().await;
+/// assert_eq!(ready.count(), 0);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct CountdownLatch {
+    count: AtomicUsize,
+    event: Event,
+}
+
+impl CountdownLatch {
+    /// Creates a latch that opens after `k` calls to [`count_down`].
+    ///
+    /// A latch created with a count of zero is open right away.
+    ///
+    /// [`count_down`]: CountdownLatch::count_down
+    pub fn new(k: usize) -> CountdownLatch {
+        CountdownLatch {
+            count: AtomicUsize::new(k),
+            event: Event::new(),
+        }
+    }
+
+    /// Decrements the count, opening the latch when it reaches zero.
+    ///
+    /// Counting down an open latch does nothing.
+    pub fn count_down(&self) {
+        let previous = self
+            .count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+        if previous == Ok(1) {
+            self.event.notify(usize::MAX);
+        }
+    }
+
+    /// Returns how many more calls to `count_down` are needed to open the latch.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Waits until the latch is open.
+    pub async fn wait(&self) {
+        loop {
+            if self.count() == 0 {
+                return;
+            }
+
+            let listener = self.event.listen();
+            if self.count() == 0 {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
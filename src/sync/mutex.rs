@@ -0,0 +1,248 @@
+use crate::{Config, SemaphoreInner};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// An async mutual exclusion lock.
+///
+/// The lock is a semaphore with a single permit. Tasks waiting for it are
+/// served in the order they arrived, and the guard returned by [`lock`] can
+/// be held across `.await` points.
+///
+/// [`lock`]: Mutex::lock
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Mutex;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let count = Arc::new(Mutex::new(0));
+///
+/// let mut tasks = Vec::new();
+/// for _ in 0..8 {
+///     let count = count.clone();
+///     tasks.push(tokio::spawn(async move {
+///         *count.lock().await += 1;
+///     }));
+/// }
+/// for task in tasks {
+///     task.await.unwrap();
+/// }
+/// assert_eq!(*count.lock().await, 8);
+/// # });
+/// ```
+pub struct Mutex<T: ?Sized> {
+    sema: SemaphoreInner,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the semaphore hands out a single permit, so at most one guard
+// accesses the data at a time.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex holding `value`.
+    pub fn new(value: T) -> Mutex<T> {
+        Mutex {
+            sema: SemaphoreInner::with_config(
+                1,
+                Config {
+                    fair: true,
+                    ..Config::new()
+                },
+            ),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the mutex, returning the data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Waits until the lock is free and takes it.
+    ///
+    /// The lock is released when the returned guard is dropped.
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        self.wait().await;
+        MutexGuard { lock: self }
+    }
+
+    /// Takes the lock, returning a guard that keeps the mutex alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::Mutex;
+    /// use std::sync::Arc;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let names = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// let mut guard = names.clone().lock_owned().await;
+    /// tokio::spawn(async move { guard.push("first") }).await.unwrap();
+    ///
+    /// assert_eq!(*names.lock().await, ["first"]);
+    /// # });
+    /// ```
+    pub async fn lock_owned(self: Arc<Self>) -> OwnedMutexGuard<T> {
+        self.wait().await;
+        OwnedMutexGuard { lock: self }
+    }
+
+    /// Attempts to take the lock without waiting.
+    ///
+    /// Returns `None` if the lock is held, or if other tasks are already
+    /// waiting for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::Mutex;
+    ///
+    /// let lock = Mutex::new(1);
+    ///
+    /// let guard = lock.try_lock().unwrap();
+    /// assert!(lock.try_lock().is_none());
+    ///
+    /// drop(guard);
+    /// assert!(lock.try_lock().is_some());
+    /// ```
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.sema
+            .try_acquire_exact(1)
+            .ok()
+            .map(|()| MutexGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// No locking is needed, as the mutable borrow guarantees no guard exists.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    async fn wait(&self) {
+        // The semaphore is private to the mutex and never closed or poisoned.
+        self.sema.acquire(1).await.expect("mutex semaphore closed");
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Mutex<T> {
+        Mutex::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    fn from(value: T) -> Mutex<T> {
+        Mutex::new(value)
+    }
+}
+
+/// A guard holding a [`Mutex`] locked.
+///
+/// The lock is released when the guard is dropped.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct MutexGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+}
+
+// SAFETY: a shared guard only gives out shared references to the data.
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the only permit of the semaphore.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the only permit of the semaphore.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.sema.release(1);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// A guard holding a [`Mutex`] locked, returned by [`Mutex::lock_owned`].
+///
+/// The lock is released when the guard is dropped.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct OwnedMutexGuard<T: ?Sized> {
+    lock: Arc<Mutex<T>>,
+}
+
+// SAFETY: a shared guard only gives out shared references to the data.
+unsafe impl<T: ?Sized + Sync> Sync for OwnedMutexGuard<T> {}
+
+impl<T: ?Sized> Deref for OwnedMutexGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the only permit of the semaphore.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for OwnedMutexGuard<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the only permit of the semaphore.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for OwnedMutexGuard<T> {
+    fn drop(&mut self) {
+        self.lock.sema.release(1);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OwnedMutexGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for OwnedMutexGuard<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,120 @@
+use event_listener::{Event, IntoNotification};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Notifies tasks of an event, without passing any data or permits.
+///
+/// A call to [`notify_one`] wakes a single waiting task, or is remembered for
+/// the next call to [`notified`] if no task is waiting. [`notify_waiters`]
+/// wakes every task that is waiting at that point and is not remembered.
+///
+/// [`notify_one`]: Notify::notify_one
+/// [`notified`]: Notify::notified
+/// [`notify_waiters`]: Notify::notify_waiters
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Notify;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let notify = Arc::new(Notify::new());
+///
+/// let waiter = tokio::spawn({
+///     let notify = notify.clone();
+///     async move { notify.notified().await }
+/// });
+///
+/// notify.notify_one();
+/// waiter.await.unwrap();
+/// # });
+/// ```
+#[derive(Debug, Default)]
+pub struct Notify {
+    permit: AtomicBool,
+    generation: AtomicUsize,
+    event: Event,
+}
+
+impl Notify {
+    /// Creates a new `Notify` without a stored notification.
+    pub fn new() -> Notify {
+        Notify::default()
+    }
+
+    /// Waits for a notification.
+    ///
+    /// The task counts as waiting for [`notify_waiters`] from the moment this
+    /// method is called, even before the returned future is first polled.
+    ///
+    /// [`notify_waiters`]: Notify::notify_waiters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::Notify;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let notify = Notify::new();
+    ///
+    /// let (a, b) = (notify.notified(), notify.notified());
+    /// notify.notify_waiters();
+    /// a.await;
+    /// b.await;
+    /// # });
+    /// ```
+    pub fn notified(&self) -> impl Future<Output = ()> + '_ {
+        let generation = self.generation.load(Ordering::Acquire);
+        async move {
+            loop {
+                if self.take(generation) {
+                    return;
+                }
+
+                let listener = self.event.listen();
+                if self.take(generation) {
+                    return;
+                }
+                listener.await;
+            }
+        }
+    }
+
+    /// Wakes one waiting task, or stores a notification for the next one.
+    ///
+    /// At most one notification is stored, however often this is called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::Notify;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let notify = Notify::new();
+    ///
+    /// notify.notify_one();
+    /// notify.notify_one();
+    /// notify.notified().await;
+    /// # });
+    /// ```
+    pub fn notify_one(&self) {
+        self.permit.store(true, Ordering::Release);
+        self.event.notify(1.additional());
+    }
+
+    /// Wakes every task that is currently waiting.
+    ///
+    /// Tasks that start waiting afterwards are not affected, and no
+    /// notification is stored.
+    pub fn notify_waiters(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.event.notify(usize::MAX);
+    }
+
+    /// Consumes a notification sent since the wait for `generation` began.
+    fn take(&self, generation: usize) -> bool {
+        self.generation.load(Ordering::Acquire) != generation
+            || self.permit.swap(false, Ordering::AcqRel)
+    }
+}
@@ -0,0 +1,120 @@
+use crate::sync::Mutex;
+use std::fmt;
+use std::future::Future;
+use std::sync::OnceLock;
+
+/// A value that is initialized once, by the first task that asks for it.
+///
+/// Other tasks asking in the meantime wait for the initializer and then all
+/// get the same value. If the initializing task is cancelled, the next
+/// waiting task runs its own initializer instead.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::AsyncOnce;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let config = Arc::new(AsyncOnce::new());
+/// let loads = Arc::new(AtomicUsize::new(0));
+///
+/// let mut tasks = Vec::new();
+/// for _ in 0..4 {
+///     let (config, loads) = (config.clone(), loads.clone());
+///     tasks.push(tokio::spawn(async move {
+///         *config
+///             .get_or_init(|| async {
+///                 loads.fetch_add(1, Ordering::SeqCst);
+///                 "loaded"
+///             })
+///             .await
+///     }));
+/// }
+/// for task in tasks {
+///     assert_eq!(task.await.unwrap(), "loaded");
+/// }
+/// assert_eq!(loads.load(Ordering::SeqCst), 1);
+/// # });
+/// ```
+pub struct AsyncOnce<T> {
+    value: OnceLock<T>,
+    init: Mutex<()>,
+}
+
+impl<T> AsyncOnce<T> {
+    /// Creates an uninitialized cell.
+    pub fn new() -> AsyncOnce<T> {
+        AsyncOnce {
+            value: OnceLock::new(),
+            init: Mutex::new(()),
+        }
+    }
+
+    /// Returns the value, or `None` if it has not been initialized yet.
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Returns the value, initializing it with `f` if no task has yet.
+    ///
+    /// While another task runs its initializer, this waits for it to finish
+    /// and `f` is not called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::AsyncOnce;
+    /// use futures::poll;
+    /// use std::future::pending;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let once = AsyncOnce::new();
+    ///
+    /// let mut first = Box::pin(once.get_or_init(pending));
+    /// let mut second = Box::pin(once.get_or_init(|| async { 2 }));
+    /// assert!(poll!(&mut first).is_pending());
+    /// assert!(poll!(&mut second).is_pending());
+    ///
+    /// // The first initializer never finishes, so the second one takes over.
+    /// drop(first);
+    /// assert_eq!(*second.await, 2);
+    /// # });
+    /// ```
+    pub async fn get_or_init<F, Fut>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        if let Some(value) = self.value.get() {
+            return value;
+        }
+
+        let _init = self.init.lock().await;
+        if let Some(value) = self.value.get() {
+            return value;
+        }
+        let value = f().await;
+        self.value.get_or_init(|| value)
+    }
+
+    /// Consumes the cell, returning the value if it was initialized.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for AsyncOnce<T> {
+    fn default() -> AsyncOnce<T> {
+        AsyncOnce::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AsyncOnce<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncOnce")
+            .field("value", &self.value.get())
+            .finish()
+    }
+}
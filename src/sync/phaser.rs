@@ -0,0 +1,173 @@
+use event_listener::Event;
+use std::sync::Mutex;
+
+#[derive(Debug)]
+struct State {
+    parties: usize,
+    arrived: usize,
+    phase: u64,
+}
+
+/// A reusable barrier whose parties can register and deregister between rounds.
+///
+/// Each round is a numbered phase, starting at zero. The phase advances once
+/// every registered party has arrived, which releases the parties waiting
+/// for it. Parties may also arrive without waiting, or arrive and leave.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::Phaser;
+/// use std::sync::Arc;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let phaser = Arc::new(Phaser::new(1));
+///
+/// let mut workers = Vec::new();
+/// for rounds in 1..=3 {
+///     phaser.register();
+///     let phaser = phaser.clone();
+///     workers.push(tokio::spawn(async move {
+///         for _ in 1..rounds {
+///             phaser.arrive_and_await_advance().await;
+///         }
+///         // Leaves after its last round, so the others go on without it.
+///         phaser.arrive_and_deregister();
+///     }));
+/// }
+///
+/// // The coordinator takes part in every round until the workers are done.
+/// for phase in 1..=3 {
+///     assert_eq!(phaser.arrive_and_await_advance().await, phase);
+/// }
+/// for worker in workers {
+///     worker.await.unwrap();
+/// }
+/// assert_eq!(phaser.registered(), 1);
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Phaser {
+    state: Mutex<State>,
+    event: Event,
+}
+
+impl Phaser {
+    /// Creates a phaser with `parties` registered parties, at phase zero.
+    pub fn new(parties: usize) -> Phaser {
+        Phaser {
+            state: Mutex::new(State {
+                parties,
+                arrived: 0,
+                phase: 0,
+            }),
+            event: Event::new(),
+        }
+    }
+
+    /// Adds a party, which takes part from the current phase on.
+    ///
+    /// Returns the current phase.
+    pub fn register(&self) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.parties += 1;
+        state.phase
+    }
+
+    /// Arrives at the current phase without waiting for the others.
+    ///
+    /// Returns the phase arrived at.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all registered parties have already arrived.
+    pub fn arrive(&self) -> u64 {
+        self.arrive_and(false)
+    }
+
+    /// Arrives at the current phase and deregisters, without waiting.
+    ///
+    /// Returns the phase arrived at.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all registered parties have already arrived.
+    pub fn arrive_and_deregister(&self) -> u64 {
+        self.arrive_and(true)
+    }
+
+    /// Arrives at the current phase and waits for the other parties.
+    ///
+    /// Returns the number of the phase that was advanced to. The task
+    /// arrives when the future is first polled, so one that stops waiting
+    /// early, by dropping the future once it has been polled, still counts
+    /// as arrived. To arrive without polling, call [`arrive`] and then
+    /// [`await_advance`].
+    ///
+    /// [`arrive`]: Phaser::arrive
+    /// [`await_advance`]: Phaser::await_advance
+    ///
+    /// # Panics
+    ///
+    /// Panics if all registered parties have already arrived.
+    pub async fn arrive_and_await_advance(&self) -> u64 {
+        let phase = self.arrive();
+        self.await_advance(phase).await
+    }
+
+    /// Waits until the phaser has advanced past `phase`.
+    ///
+    /// Returns the current phase, right away if it is already past `phase`.
+    pub async fn await_advance(&self, phase: u64) -> u64 {
+        loop {
+            let current = self.phase();
+            if current != phase {
+                return current;
+            }
+
+            let listener = self.event.listen();
+            let current = self.phase();
+            if current != phase {
+                return current;
+            }
+            listener.await;
+        }
+    }
+
+    /// Returns the current phase.
+    pub fn phase(&self) -> u64 {
+        self.state.lock().unwrap().phase
+    }
+
+    /// Returns the number of registered parties.
+    pub fn registered(&self) -> usize {
+        self.state.lock().unwrap().parties
+    }
+
+    /// Returns the number of parties that arrived at the current phase.
+    pub fn arrived(&self) -> usize {
+        self.state.lock().unwrap().arrived
+    }
+
+    fn arrive_and(&self, deregister: bool) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        assert!(
+            state.arrived < state.parties,
+            "more arrivals than registered parties"
+        );
+        let phase = state.phase;
+        if deregister {
+            state.parties -= 1;
+        } else {
+            state.arrived += 1;
+        }
+
+        if state.parties > 0 && state.arrived == state.parties {
+            state.arrived = 0;
+            state.phase += 1;
+            drop(state);
+            self.event.notify(usize::MAX);
+        }
+        phase
+    }
+}
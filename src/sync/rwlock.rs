@@ -0,0 +1,281 @@
+use crate::{Config, Permits, SemaphoreInner};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// The permits of the semaphore, and so the number of concurrent readers.
+const MAX_READS: Permits = (u32::MAX >> 3) as Permits;
+
+/// An async reader-writer lock.
+///
+/// The lock is a semaphore with a permit for each concurrent reader, up to
+/// `u32::MAX >> 3` of them. A reader takes one permit, a writer takes them all.
+/// Tasks waiting for the lock are served in the order they arrived, so a
+/// waiting writer holds back readers that come after it.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::RwLock;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let lock = RwLock::new(5);
+///
+/// let (a, b) = (lock.read().await, lock.read().await);
+/// assert_eq!(*a + *b, 10);
+/// assert!(lock.try_write().is_none());
+/// drop((a, b));
+///
+/// *lock.write().await += 1;
+/// assert_eq!(*lock.read().await, 6);
+/// # });
+/// ```
+pub struct RwLock<T: ?Sized> {
+    sema: SemaphoreInner,
+    prefer_writers: bool,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: a writer holds all permits of the semaphore, so it never accesses
+// the data at the same time as another writer or a reader.
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new unlocked lock holding `value`.
+    pub fn new(value: T) -> RwLock<T> {
+        RwLock {
+            sema: SemaphoreInner::with_config(
+                MAX_READS,
+                Config {
+                    fair: true,
+                    ..Config::new()
+                },
+            ),
+            prefer_writers: false,
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Sets whether waiting writers are served before waiting readers.
+    ///
+    /// By default readers and writers take turns in the order they arrived.
+    /// Preferring writers lets a writer overtake all readers that are still
+    /// waiting, which keeps writes timely under a steady stream of reads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::RwLock;
+    /// use futures::poll;
+    /// use std::pin::pin;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let lock = RwLock::new(0).prefer_writers(true);
+    /// let held = lock.write().await;
+    ///
+    /// let mut reader = pin!(lock.read());
+    /// let mut writer = pin!(async { *lock.write().await = 1 });
+    /// assert!(poll!(&mut reader).is_pending());
+    /// assert!(poll!(&mut writer).is_pending());
+    ///
+    /// // The reader queued first, but the waiting writer goes ahead of it.
+    /// drop(held);
+    /// writer.await;
+    /// assert_eq!(*reader.await, 1);
+    /// # });
+    /// ```
+    pub fn prefer_writers(mut self, prefer: bool) -> RwLock<T> {
+        self.prefer_writers = prefer;
+        self
+    }
+
+    /// Consumes the lock, returning the data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Waits until no writer holds the lock and takes a shared read lock.
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.wait(1, 0).await;
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Waits until no one holds the lock and takes it exclusively.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.wait(MAX_READS, u8::from(self.prefer_writers)).await;
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Attempts to take a read lock without waiting.
+    ///
+    /// Returns `None` if a writer holds the lock, or if other tasks are
+    /// already waiting for it.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.sema
+            .try_acquire_exact(1)
+            .ok()
+            .map(|()| RwLockReadGuard { lock: self })
+    }
+
+    /// Attempts to take the write lock without waiting.
+    ///
+    /// Returns `None` if the lock is held, or if other tasks are already
+    /// waiting for it.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.sema
+            .try_acquire_exact(MAX_READS)
+            .ok()
+            .map(|()| RwLockWriteGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// No locking is needed, as the mutable borrow guarantees no guard exists.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    async fn wait(&self, permits: Permits, priority: u8) {
+        // The semaphore is private to the lock and never closed or poisoned.
+        self.sema
+            .acquire(permits)
+            .with_priority(priority)
+            .await
+            .expect("lock semaphore closed");
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(T::default())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    fn from(value: T) -> RwLock<T> {
+        RwLock::new(value)
+    }
+}
+
+/// A guard holding a shared read lock on a [`RwLock`].
+///
+/// The lock is released when the guard is dropped.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: no writer holds the lock while a read permit is held.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.sema.release(1);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// A guard holding the exclusive write lock on a [`RwLock`].
+///
+/// The lock is released when the guard is dropped.
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+// SAFETY: a shared guard only gives out shared references to the data.
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    /// Turns the write lock into a read lock, letting other readers in.
+    ///
+    /// No writer can take the lock in between.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::sync::{RwLock, RwLockWriteGuard};
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let lock = RwLock::new(1);
+    ///
+    /// let mut write = lock.write().await;
+    /// *write = 2;
+    /// let read = RwLockWriteGuard::downgrade(write);
+    /// assert_eq!(*lock.try_read().unwrap(), 2);
+    /// assert!(lock.try_write().is_none());
+    /// # drop(read);
+    /// # });
+    /// ```
+    pub fn downgrade(this: Self) -> RwLockReadGuard<'a, T> {
+        let lock = this.lock;
+        std::mem::forget(this);
+        lock.sema.release(MAX_READS - 1);
+        RwLockReadGuard { lock }
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds all permits of the semaphore.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds all permits of the semaphore.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.sema.release(MAX_READS);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
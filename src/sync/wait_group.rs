@@ -0,0 +1,117 @@
+use event_listener::Event;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Default)]
+struct Inner {
+    count: AtomicUsize,
+    event: Event,
+}
+
+/// Waits for a group of tasks to finish.
+///
+/// The group counts outstanding work: [`add`] raises the count, [`done`]
+/// lowers it, and [`wait`] completes once it is back at zero. Clones share
+/// the same count.
+///
+/// [`add`]: WaitGroup::add
+/// [`done`]: WaitGroup::done
+/// [`wait`]: WaitGroup::wait
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::sync::WaitGroup;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let group = WaitGroup::new();
+///
+/// for _ in 0..4 {
+///     let worker = group.worker();
+///     tokio::spawn(async move {
+///         // Do the work, then drop the guard to report it done.
+///         drop(worker);
+///     });
+/// }
+///
+/// group.wait().await;
+/// assert_eq!(group.count(), 0);
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WaitGroup {
+    inner: Arc<Inner>,
+}
+
+impl WaitGroup {
+    /// Creates a group with no outstanding work.
+    pub fn new() -> WaitGroup {
+        WaitGroup::default()
+    }
+
+    /// Adds `n` units of outstanding work.
+    pub fn add(&self, n: usize) {
+        self.inner.count.fetch_add(n, Ordering::AcqRel);
+    }
+
+    /// Marks one unit of work as done.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no outstanding work.
+    pub fn done(&self) {
+        let previous = self
+            .inner
+            .count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .expect("`done` called on a wait group without outstanding work");
+        if previous == 1 {
+            self.inner.event.notify(usize::MAX);
+        }
+    }
+
+    /// Adds one unit of work, returning a guard that marks it done when dropped.
+    pub fn worker(&self) -> WaitGroupGuard {
+        self.add(1);
+        WaitGroupGuard {
+            group: self.clone(),
+        }
+    }
+
+    /// Returns the number of outstanding units of work.
+    pub fn count(&self) -> usize {
+        self.inner.count.load(Ordering::Acquire)
+    }
+
+    /// Waits until there is no outstanding work.
+    ///
+    /// Completes right away if the count is already zero.
+    pub async fn wait(&self) {
+        loop {
+            if self.count() == 0 {
+                return;
+            }
+
+            let listener = self.inner.event.listen();
+            if self.count() == 0 {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
+
+/// A unit of work in a [`WaitGroup`], returned by [`WaitGroup::worker`].
+///
+/// The work is marked done when the guard is dropped.
+#[must_use = "the work is marked done as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct WaitGroupGuard {
+    group: WaitGroup,
+}
+
+impl Drop for WaitGroupGuard {
+    fn drop(&mut self) {
+        self.group.done();
+    }
+}
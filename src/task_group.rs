@@ -0,0 +1,115 @@
+use crate::sync::WaitGroup;
+use crate::{AcquireError, Permits, Semaphore};
+use std::fmt;
+use std::future::Future;
+
+/// A group of child tasks running at most as many at once as its semaphore
+/// has permits.
+///
+/// Each child is run through [`run`], which waits for a permit and holds it
+/// while the child runs. [`join`] waits until every child has finished,
+/// including those still waiting for a permit, so the group outlives all of
+/// its children. Clones share the same limit and children, and the group
+/// works with any runtime: the children run wherever `run` is awaited.
+///
+/// [`run`]: TaskGroup::run
+/// [`join`]: TaskGroup::join
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::TaskGroup;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let group = TaskGroup::new(2);
+///
+/// for n in 0..8 {
+///     tokio::spawn(group.run(async move { n * 2 }));
+/// }
+/// assert_eq!(group.running(), 8);
+///
+/// group.join().await;
+/// assert_eq!(group.running(), 0);
+/// assert_eq!(group.semaphore().available_permits(), 2);
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct TaskGroup {
+    sema: Semaphore,
+    children: WaitGroup,
+}
+
+impl TaskGroup {
+    /// Creates a group running up to `limit` children at once.
+    pub fn new(limit: Permits) -> TaskGroup {
+        TaskGroup::with_semaphore(Semaphore::new(limit))
+    }
+
+    /// Creates a group running each child under a permit of `sema`.
+    pub fn with_semaphore(sema: Semaphore) -> TaskGroup {
+        TaskGroup {
+            sema,
+            children: WaitGroup::new(),
+        }
+    }
+
+    /// Runs `future` as a child of the group, once a permit is free for it.
+    ///
+    /// The child counts towards [`join`](TaskGroup::join) from the moment
+    /// this is called, rather than when the returned future is first
+    /// polled, so a child spawned onto another task is waited for even if
+    /// that task has not started yet. The child holds its permit until it
+    /// completes or is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_sema::TaskGroup;
+    ///
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// let group = TaskGroup::new(1);
+    ///
+    /// let (a, b) = futures::join!(group.run(async { 1 }), group.run(async { 2 }));
+    /// assert_eq!((a, b), (Ok(1), Ok(2)));
+    /// # });
+    /// ```
+    pub fn run<F: Future>(
+        &self,
+        future: F,
+    ) -> impl Future<Output = Result<F::Output, AcquireError>> {
+        let child = self.children.worker();
+        let sema = self.sema.clone();
+        async move {
+            // Dropped after the permit, so `join` returns with every permit back.
+            let _child = child;
+            let _permit = sema.acquire().await?;
+            Ok(future.await)
+        }
+    }
+
+    /// Waits until every child of the group has finished.
+    ///
+    /// Completes right away if no child is running or waiting.
+    pub async fn join(&self) {
+        self.children.wait().await;
+    }
+
+    /// Returns the number of children running or waiting for a permit.
+    pub fn running(&self) -> usize {
+        self.children.count()
+    }
+
+    /// Returns the semaphore limiting the running children.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.sema
+    }
+}
+
+impl fmt::Debug for TaskGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskGroup")
+            .field("running", &self.running())
+            .field("available", &self.sema.available_permits())
+            .finish()
+    }
+}
@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, Weak};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Instant;
+
+/// A registered deadline, shared between a `Sleep` and the timer thread.
+#[derive(Debug)]
+struct Entry {
+    deadline: Instant,
+    waker: Mutex<Option<Waker>>,
+}
+
+/// Heap item ordered so that the earliest deadline is popped first.
+struct Scheduled(Instant, Weak<Entry>);
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
+/// A background thread waking sleepers once their deadline passes.
+///
+/// The thread is spawned on first use so the crate works with any executor.
+/// With the `tokio` feature, sleeps within a tokio runtime use its timer
+/// instead.
+struct Timer {
+    heap: Mutex<Heap>,
+    cond: Condvar,
+}
+
+/// The fewest entries the heap holds before it is pruned.
+const MIN_PRUNE: usize = 64;
+
+struct Heap {
+    entries: BinaryHeap<Scheduled>,
+    /// The length at which entries of dropped sleeps are pruned, twice the
+    /// live entries left after the last pruning.
+    prune_at: usize,
+}
+
+impl Timer {
+    fn get() -> &'static Timer {
+        static TIMER: OnceLock<Timer> = OnceLock::new();
+        static THREAD: OnceLock<()> = OnceLock::new();
+
+        let timer = TIMER.get_or_init(|| Timer {
+            heap: Mutex::new(Heap {
+                entries: BinaryHeap::new(),
+                prune_at: MIN_PRUNE,
+            }),
+            cond: Condvar::new(),
+        });
+        THREAD.get_or_init(|| {
+            thread::Builder::new()
+                .name("async-sema-timer".into())
+                .spawn(move || timer.run())
+                .expect("failed to spawn timer thread");
+        });
+        timer
+    }
+
+    fn register(&self, entry: &Arc<Entry>) {
+        let mut heap = self.heap.lock().unwrap();
+        // Cancelled sleeps would otherwise stay until their deadline, so
+        // many long timeouts that never fire would grow the heap unbounded.
+        if heap.entries.len() >= heap.prune_at {
+            heap.entries
+                .retain(|Scheduled(_, entry)| entry.strong_count() > 0);
+            heap.prune_at = MIN_PRUNE.max(heap.entries.len() * 2);
+        }
+        heap.entries
+            .push(Scheduled(entry.deadline, Arc::downgrade(entry)));
+        self.cond.notify_one();
+    }
+
+    fn run(&self) {
+        let mut heap = self.heap.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            match heap.entries.peek() {
+                None => heap = self.cond.wait(heap).unwrap(),
+                Some(Scheduled(deadline, _)) if *deadline > now => {
+                    let timeout = *deadline - now;
+                    heap = self.cond.wait_timeout(heap, timeout).unwrap().0;
+                }
+                Some(_) => {
+                    let Scheduled(_, entry) = heap.entries.pop().unwrap();
+                    let waker = entry.upgrade().and_then(|e| e.waker.lock().unwrap().take());
+                    if let Some(waker) = waker {
+                        // Wakers may register new deadlines, so the heap must be unlocked.
+                        drop(heap);
+                        waker.wake();
+                        heap = self.heap.lock().unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the current time, as seen by the installed clock if there is one.
+///
+/// With the `tokio` feature this is tokio's clock, which follows paused time.
+pub(crate) fn now() -> Instant {
+    #[cfg(feature = "sim")]
+    if let Some(clock) = crate::sim::clock() {
+        return clock.now();
+    }
+    #[cfg(feature = "tokio")]
+    return tokio::time::Instant::now().into_std();
+
+    #[cfg(not(feature = "tokio"))]
+    Instant::now()
+}
+
+/// Returns whether deadlines are timed by the current tokio runtime.
+///
+/// With the `tokio` feature, code running within a tokio runtime uses its
+/// timer, unless a simulation clock is installed.
+#[cfg(feature = "tokio")]
+fn on_tokio() -> bool {
+    #[cfg(feature = "sim")]
+    if crate::sim::clock().is_some() {
+        return false;
+    }
+    tokio::runtime::Handle::try_current().is_ok()
+}
+
+/// Arranges for `entry` to be woken once its deadline has passed.
+fn schedule(entry: &Arc<Entry>) {
+    #[cfg(feature = "sim")]
+    if let Some(clock) = crate::sim::clock() {
+        let waker = Waker::from(Arc::new(WeakEntry(Arc::downgrade(entry))));
+        return clock.wake_at(entry.deadline, waker);
+    }
+    Timer::get().register(entry);
+}
+
+/// Forwards a wakeup from an installed clock, unless the entry was dropped.
+#[cfg(feature = "sim")]
+struct WeakEntry(Weak<Entry>);
+
+#[cfg(feature = "sim")]
+impl std::task::Wake for WeakEntry {
+    fn wake(self: Arc<Self>) {
+        if let Some(entry) = self.0.upgrade() {
+            if let Some(waker) = entry.waker.lock().unwrap().take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Wakes a waker once its deadline has passed, unless dropped before.
+#[derive(Debug)]
+pub(crate) struct Alarm {
+    _timer: AlarmTimer,
+}
+
+#[derive(Debug)]
+enum AlarmTimer {
+    Thread {
+        _entry: Arc<Entry>,
+    },
+    /// A task on the tokio runtime sleeping until the deadline.
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::task::AbortHandle),
+}
+
+impl Alarm {
+    pub fn new(deadline: Instant, waker: Waker) -> Self {
+        #[cfg(feature = "tokio")]
+        if on_tokio() {
+            let sleep = tokio::time::sleep_until(deadline.into());
+            let task = tokio::spawn(async move {
+                sleep.await;
+                waker.wake();
+            });
+            return Self {
+                _timer: AlarmTimer::Tokio(task.abort_handle()),
+            };
+        }
+        let entry = Arc::new(Entry {
+            deadline,
+            waker: Mutex::new(Some(waker)),
+        });
+        schedule(&entry);
+        Self {
+            _timer: AlarmTimer::Thread { _entry: entry },
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for AlarmTimer {
+    fn drop(&mut self) {
+        if let AlarmTimer::Tokio(task) = self {
+            task.abort();
+        }
+    }
+}
+
+/// A future completing once `deadline` has passed.
+#[derive(Debug)]
+pub(crate) struct Sleep {
+    deadline: Instant,
+    timer: Option<SleepTimer>,
+}
+
+#[derive(Debug)]
+enum SleepTimer {
+    Thread(Arc<Entry>),
+    #[cfg(feature = "tokio")]
+    Tokio(Pin<Box<tokio::time::Sleep>>),
+}
+
+impl Sleep {
+    pub fn until(deadline: Instant) -> Self {
+        Self {
+            deadline,
+            timer: None,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        let deadline = self.deadline;
+        match self
+            .timer
+            .get_or_insert_with(|| SleepTimer::new(deadline, cx.waker()))
+        {
+            SleepTimer::Thread(entry) => *entry.waker.lock().unwrap() = Some(cx.waker().clone()),
+            #[cfg(feature = "tokio")]
+            SleepTimer::Tokio(sleep) => return sleep.as_mut().poll(cx),
+        }
+        Poll::Pending
+    }
+}
+
+impl SleepTimer {
+    fn new(deadline: Instant, waker: &Waker) -> Self {
+        #[cfg(feature = "tokio")]
+        if on_tokio() {
+            return SleepTimer::Tokio(Box::pin(tokio::time::sleep_until(deadline.into())));
+        }
+        let entry = Arc::new(Entry {
+            deadline,
+            waker: Mutex::new(Some(waker.clone())),
+        });
+        schedule(&entry);
+        SleepTimer::Thread(entry)
+    }
+}
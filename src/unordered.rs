@@ -0,0 +1,119 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+/// A set of futures run concurrently, yielding their outputs as they finish.
+///
+/// Each future is polled with a waker of its own, so a wakeup only polls the
+/// future it was meant for rather than the whole set. Every future carries a
+/// value, such as the permit it runs under, handed back with its output.
+pub(crate) struct Unordered<F, T> {
+    slots: Vec<Option<Slot<F, T>>>,
+    free: Vec<usize>,
+    ready: Arc<Ready>,
+}
+
+struct Slot<F, T> {
+    future: Pin<Box<F>>,
+    value: T,
+    waker: Waker,
+}
+
+/// The slots woken since they were last polled, and the task to wake for them.
+#[derive(Default)]
+struct Ready {
+    queue: Mutex<(Vec<usize>, Option<Waker>)>,
+}
+
+struct SlotWaker {
+    index: usize,
+    ready: Arc<Ready>,
+}
+
+impl Wake for SlotWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut queue = self.ready.queue.lock().unwrap();
+        queue.0.push(self.index);
+        if let Some(waker) = queue.1.take() {
+            drop(queue);
+            waker.wake();
+        }
+    }
+}
+
+impl<F: Future, T> Unordered<F, T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            ready: Arc::default(),
+        }
+    }
+
+    /// Returns the number of futures still running.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a future, to be polled on the next call to `poll_next`.
+    pub fn push(&mut self, future: F, value: T) {
+        let index = self.free.pop().unwrap_or(self.slots.len());
+        let waker = Waker::from(Arc::new(SlotWaker {
+            index,
+            ready: self.ready.clone(),
+        }));
+        let slot = Slot {
+            future: Box::pin(future),
+            value,
+            waker,
+        };
+        if index == self.slots.len() {
+            self.slots.push(Some(slot));
+        } else {
+            self.slots[index] = Some(slot);
+        }
+        self.ready.queue.lock().unwrap().0.push(index);
+    }
+
+    /// Polls the futures woken since the last call, returning the output of
+    /// the first one to finish along with its value.
+    ///
+    /// Returns `None` once the set is empty.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<(F::Output, T)>> {
+        if self.is_empty() {
+            return Poll::Ready(None);
+        }
+        let woken = {
+            let mut queue = self.ready.queue.lock().unwrap();
+            queue.1 = Some(cx.waker().clone());
+            std::mem::take(&mut queue.0)
+        };
+
+        let mut woken = woken.into_iter();
+        while let Some(index) = woken.next() {
+            // A wakeup may outlive its future, or come twice.
+            let Some(slot) = &mut self.slots[index] else {
+                continue;
+            };
+            let waker = slot.waker.clone();
+            if let Poll::Ready(output) = slot.future.as_mut().poll(&mut Context::from_waker(&waker))
+            {
+                let slot = self.slots[index].take().unwrap();
+                self.free.push(index);
+                // Leaves the remaining wakeups for the next call.
+                self.ready.queue.lock().unwrap().0.extend(woken);
+                return Poll::Ready(Some((output, slot.value)));
+            }
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{Permits, SemaphoreInner};
+use futures_core::Stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Stream returned by [`Semaphore::watch`].
+///
+/// Yields the number of available permits, first the current value and then
+/// whenever it changes. Like a watch channel, only the latest value is kept,
+/// so a slow consumer may skip intermediate counts. The stream never ends.
+///
+/// # Examples
+///
+/// ```
+/// use async_sema::Semaphore;
+/// use futures::StreamExt;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let s = Semaphore::new(2);
+/// let mut watch = s.watch();
+/// assert_eq!(watch.next().await, Some(2));
+///
+/// let guard = s.acquire().await.unwrap();
+/// assert_eq!(watch.next().await, Some(1));
+///
+/// drop(guard);
+/// assert_eq!(watch.next().await, Some(2));
+/// # });
+/// ```
+///
+/// [`Semaphore::watch`]: crate::Semaphore::watch
+#[must_use = "streams do nothing unless polled"]
+#[derive(Debug)]
+pub struct Watch {
+    inner: Arc<SemaphoreInner>,
+    last: Option<Permits>,
+}
+
+impl Watch {
+    pub(crate) fn new(inner: Arc<SemaphoreInner>) -> Self {
+        Self { inner, last: None }
+    }
+}
+
+impl Stream for Watch {
+    type Item = Permits;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Permits>> {
+        let this = &mut *self;
+        this.inner.poll_changed(cx, 0, || {
+            let count = this.inner.available_permits();
+            if this.last == Some(count) {
+                return Poll::Pending;
+            }
+            this.last = Some(count);
+            Poll::Ready(Some(count))
+        })
+    }
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "tokio")]
+
+use async_sema::Semaphore;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[tokio::test(flavor = "current_thread")]
+async fn acquire_loop_yields() {
+    let s = Semaphore::new(1);
+    let ran = Arc::new(AtomicBool::new(false));
+
+    tokio::spawn({
+        let ran = ran.clone();
+        async move { ran.store(true, Ordering::Relaxed) }
+    });
+
+    // Every acquire succeeds right away, only the budget lets the other task run.
+    for _ in 0..100_000 {
+        if ran.load(Ordering::Relaxed) {
+            break;
+        }
+        drop(s.acquire().await.unwrap());
+    }
+    assert!(ran.load(Ordering::Relaxed));
+}
@@ -0,0 +1,201 @@
+//! Checks that tasks waiting on each other, or on themselves, are reported.
+//!
+//! Run with `cargo test --features deadlock-detection`.
+
+#![cfg(feature = "deadlock-detection")]
+
+use async_sema::{deadlocks, Semaphore, StreamExt as _};
+use futures::StreamExt;
+
+async fn until_waiting(s: &Semaphore) {
+    while s.num_waiters() == 0 {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test]
+async fn crossed_acquires() {
+    let (s1, s2) = (Semaphore::new(1), Semaphore::new(1));
+    let (held1, held2) = (Semaphore::new(0), Semaphore::new(0));
+
+    let a = tokio::spawn({
+        let (s1, s2, held1, held2) = (s1.clone(), s2.clone(), held1.clone(), held2.clone());
+        async move {
+            let _g = s1.acquire().await.unwrap();
+            held1.add_permits(1);
+            held2.acquire_unguarded().await.unwrap();
+            let _g = s2.acquire().await.unwrap();
+        }
+    });
+    let b = tokio::spawn({
+        let (s1, s2, held1, held2) = (s1.clone(), s2.clone(), held1.clone(), held2.clone());
+        async move {
+            let _g = s2.acquire().await.unwrap();
+            held2.add_permits(1);
+            held1.acquire_unguarded().await.unwrap();
+            let _g = s1.acquire().await.unwrap();
+        }
+    });
+    until_waiting(&s1).await;
+    until_waiting(&s2).await;
+
+    let found: Vec<_> = deadlocks()
+        .into_iter()
+        .filter(|d| d.involves(&s1))
+        .collect();
+    assert_eq!(found.len(), 1);
+    assert!(found[0].involves(&s2));
+    assert_eq!(found[0].tasks().len(), 2);
+    assert!(found[0].to_string().contains("waits on semaphore"));
+
+    a.abort();
+    b.abort();
+    let _ = (a.await, b.await);
+    assert!(!deadlocks().iter().any(|d| d.involves(&s1)));
+}
+
+#[tokio::test]
+async fn plain_waiting_is_no_deadlock() {
+    let s = Semaphore::new(1);
+
+    let guard = s.acquire().await.unwrap();
+    let waiter = tokio::spawn({
+        let s = s.clone();
+        async move { drop(s.acquire().await.unwrap()) }
+    });
+    until_waiting(&s).await;
+    assert!(!deadlocks().iter().any(|d| d.involves(&s)));
+
+    drop(guard);
+    waiter.await.unwrap();
+}
+
+#[tokio::test]
+async fn owned_permits_are_tracked() {
+    let (s1, s2) = (Semaphore::new(1), Semaphore::new(1));
+
+    let g1 = s1.acquire_owned().await.unwrap();
+    let b = tokio::spawn({
+        let (s1, s2) = (s1.clone(), s2.clone());
+        async move {
+            let _g = s2.acquire_owned().await.unwrap();
+            let _g = s1.acquire_owned().await.unwrap();
+        }
+    });
+    until_waiting(&s1).await;
+    let mut wait = s2.acquire();
+    assert!(futures::poll!(&mut wait).is_pending());
+    assert!(deadlocks()
+        .iter()
+        .any(|d| d.involves(&s1) && d.involves(&s2)));
+
+    drop(wait);
+    drop(g1);
+    b.await.unwrap();
+}
+
+#[tokio::test]
+async fn reentrant_acquire_is_reported() {
+    let s = Semaphore::new(1);
+
+    let guard = s.acquire().await.unwrap();
+    let mut again = s.acquire();
+    assert!(futures::poll!(&mut again).is_pending());
+    let found: Vec<_> = deadlocks().into_iter().filter(|d| d.involves(&s)).collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].tasks().len(), 1);
+
+    drop(guard);
+    drop(again.await.unwrap());
+    assert!(!deadlocks().iter().any(|d| d.involves(&s)));
+}
+
+#[tokio::test]
+async fn reacquire_after_moving_an_owned_permit() {
+    let s = Semaphore::new(1);
+
+    // The permit stays attributed to this task once it moves to another.
+    let permit = s.acquire_owned().await.unwrap();
+    let other = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        drop(permit);
+    });
+
+    let again = s.acquire().await.unwrap();
+    drop(again);
+    other.await.unwrap();
+}
+
+#[tokio::test]
+async fn reacquire_with_spare_capacity() {
+    let s = Semaphore::new(2);
+
+    let guard = s.acquire().await.unwrap();
+    let other = tokio::spawn({
+        let s = s.clone();
+        async move {
+            let _g = s.acquire().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    });
+    while s.available_permits() > 0 {
+        tokio::task::yield_now().await;
+    }
+
+    // The other task gives its permit back, so waiting here is fine.
+    let again = s.acquire().await.unwrap();
+    drop((guard, again));
+    other.await.unwrap();
+}
+
+#[tokio::test]
+async fn stream_adapters_are_no_deadlock() {
+    let s = Semaphore::new(2);
+
+    let mut outputs = futures::stream::iter(0..8).map_concurrent_limited(&s, |i| async move {
+        tokio::task::yield_now().await;
+        i
+    });
+    let mut sum = 0;
+    while let Some(i) = outputs.next().await {
+        assert!(!deadlocks().iter().any(|d| d.involves(&s)));
+        sum += i.unwrap();
+    }
+    assert_eq!(sum, 28);
+}
+
+#[tokio::test]
+async fn dispatching_permits_is_no_deadlock() {
+    let s = Semaphore::new(1);
+    let mut permits = s.permits();
+
+    // The worker owns the permit, not the task dispatching it.
+    let worker = permits.next().await.unwrap();
+    assert!(futures::poll!(permits.next()).is_pending());
+    assert!(!deadlocks().iter().any(|d| d.involves(&s)));
+
+    drop(worker);
+    assert!(permits.next().await.is_some());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn spawning_limited_tasks_is_no_deadlock() {
+    let s = Semaphore::new(1);
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    let task = s
+        .spawn_limited(async move {
+            let _ = rx.await;
+        })
+        .await
+        .unwrap();
+
+    let next = s.spawn_limited(async {});
+    tokio::pin!(next);
+    assert!(futures::poll!(next.as_mut()).is_pending());
+    assert!(!deadlocks().iter().any(|d| d.involves(&s)));
+
+    tx.send(()).unwrap();
+    task.await.unwrap();
+    next.await.unwrap().await.unwrap();
+}
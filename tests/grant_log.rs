@@ -0,0 +1,77 @@
+//! Checks the order in which queued waiters are served, using the grant log.
+//!
+//! Run with `cargo test --features grant-log`.
+
+#![cfg(feature = "grant-log")]
+
+use async_sema::{Grant, Permits, Semaphore, WakeOrder};
+use tokio::task::JoinHandle;
+
+/// Queues one waiter per entry of `counts`, so waiter `i` asks for `counts[i]`.
+async fn queue_waiters(s: &Semaphore, counts: &[Permits]) -> Vec<JoinHandle<()>> {
+    let mut tasks = Vec::new();
+    for &count in counts {
+        let queued = s.num_waiters();
+        let task = s.clone();
+        tasks.push(tokio::spawn(async move {
+            task.batch_acquire(count).await.unwrap();
+        }));
+        while s.num_waiters() == queued {
+            tokio::task::yield_now().await;
+        }
+    }
+    tasks
+}
+
+fn waiters(grants: &[Grant]) -> Vec<u64> {
+    grants.iter().map(|grant| grant.waiter).collect()
+}
+
+#[tokio::test]
+async fn fair_grants_in_arrival_order() {
+    let s = Semaphore::builder().fair(true).build();
+    let tasks = queue_waiters(&s, &[2, 1, 1]).await;
+
+    s.add_permits(4);
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let grants = s.take_grant_log();
+    assert_eq!(waiters(&grants), [0, 1, 2]);
+    assert_eq!(grants[0].permits, 2);
+    assert!(s.take_grant_log().is_empty());
+}
+
+#[tokio::test]
+async fn fair_head_blocks_later_waiters() {
+    let s = Semaphore::builder().fair(true).build();
+    let tasks = queue_waiters(&s, &[3, 1]).await;
+
+    s.add_permits(1);
+    assert!(s.take_grant_log().is_empty());
+
+    s.add_permits(2);
+    assert_eq!(waiters(&s.take_grant_log()), [0]);
+
+    s.add_permits(1);
+    assert_eq!(waiters(&s.take_grant_log()), [1]);
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn lifo_grants_latest_first() {
+    let s = Semaphore::builder().wake_order(WakeOrder::Lifo).build();
+    let tasks = queue_waiters(&s, &[1, 1, 1]).await;
+
+    for _ in 0..3 {
+        s.add_permits(1);
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    assert_eq!(waiters(&s.take_grant_log()), [2, 1, 0]);
+}
@@ -0,0 +1,95 @@
+//! Model checks of the semaphore core under every interleaving loom explores.
+//!
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --test loom --release`.
+
+#![cfg(loom)]
+
+use async_sema::Semaphore;
+use loom::future::block_on;
+use loom::thread;
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll};
+
+#[test]
+fn acquire_release() {
+    loom::model(|| {
+        let s = Semaphore::new(1);
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let s = s.clone();
+                thread::spawn(move || {
+                    let permit = block_on(s.acquire()).unwrap();
+                    drop(permit);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(s.available_permits(), 1);
+        assert_eq!(s.num_waiters(), 0);
+    });
+}
+
+#[test]
+fn add_permits_wakes_waiter() {
+    loom::model(|| {
+        let s = Semaphore::new(0);
+
+        let waiter = thread::spawn({
+            let s = s.clone();
+            move || block_on(s.acquire_many(2)).unwrap().forget()
+        });
+        s.add_permits(1);
+        s.add_permits(1);
+        waiter.join().unwrap();
+
+        assert_eq!(s.available_permits(), 0);
+    });
+}
+
+#[test]
+fn batch_acquires_share_releases() {
+    loom::model(|| {
+        let s = Semaphore::new(2);
+
+        let permit = block_on(s.acquire_many(2)).unwrap();
+        let waiter = thread::spawn({
+            let s = s.clone();
+            move || drop(block_on(s.acquire_many(2)).unwrap())
+        });
+        drop(permit);
+        waiter.join().unwrap();
+
+        assert_eq!(s.available_permits(), 2);
+    });
+}
+
+#[test]
+fn cancel_races_release() {
+    loom::model(|| {
+        let s = Semaphore::new(0);
+
+        let releaser = thread::spawn({
+            let s = s.clone();
+            move || s.add_permits(1)
+        });
+
+        // A queued acquire that is dropped must never keep a granted permit.
+        {
+            let mut acquire = pin!(s.acquire());
+            let waker = futures::task::noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            if let Poll::Ready(permit) = acquire.as_mut().poll(&mut cx) {
+                drop(permit.unwrap());
+            }
+        }
+        releaser.join().unwrap();
+
+        assert_eq!(s.available_permits(), 1);
+        assert_eq!(s.num_waiters(), 0);
+    });
+}
@@ -0,0 +1,275 @@
+//! Timed operations under tokio's paused clock, standing in for a simulation runtime.
+//!
+//! Run with `cargo test --features sim`.
+
+#![cfg(feature = "sim")]
+
+use async_sema::limit::{
+    CircuitBreaker, CircuitState, LeakyBucket, Limiter, Outcome, RateLimiter, SlidingWindow,
+};
+use async_sema::sim::{set_clock, Clock};
+use async_sema::{AcquireError, KeyedSemaphore, Semaphore};
+use std::sync::Arc;
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn wake_at(&self, deadline: Instant, waker: Waker) {
+        tokio::spawn(async move {
+            tokio::time::sleep_until(deadline.into()).await;
+            waker.wake();
+        });
+    }
+}
+
+fn install() {
+    let _ = set_clock(TokioClock);
+}
+
+#[tokio::test(start_paused = true)]
+async fn timeout_follows_simulated_time() {
+    install();
+    let s = Semaphore::new(0);
+
+    let real = Instant::now();
+    let start = tokio::time::Instant::now();
+    let res = s.acquire_timeout(1, Duration::from_secs(3600)).await;
+
+    assert_eq!(res.unwrap_err(), AcquireError::Timeout);
+    assert!(start.elapsed() >= Duration::from_secs(3600));
+    assert!(real.elapsed() < Duration::from_secs(60));
+    assert_eq!(s.num_waiters(), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn permit_released_before_deadline() {
+    install();
+    let s = Semaphore::new(0);
+
+    let releaser = tokio::spawn({
+        let s = s.clone();
+        async move {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            s.add_permits(1);
+        }
+    });
+
+    let start = tokio::time::Instant::now();
+    let permit = s.acquire_timeout(1, Duration::from_secs(60)).await.unwrap();
+    assert_eq!(permit.num_permits(), 1);
+    assert_eq!(start.elapsed(), Duration::from_secs(10));
+    releaser.await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn lease_expires_in_simulated_time() {
+    install();
+    let s = Semaphore::new(1);
+
+    let lease = s.acquire_lease(1, Duration::from_secs(600)).await.unwrap();
+    tokio::time::sleep(Duration::from_secs(300)).await;
+    lease.renew().unwrap();
+
+    tokio::time::sleep(Duration::from_secs(599)).await;
+    assert!(!lease.is_expired());
+
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(lease.is_expired());
+    assert_eq!(s.available_permits(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn rate_limiter_refills_in_simulated_time() {
+    install();
+    let limiter = RateLimiter::new(Duration::from_secs(10), 2);
+
+    let start = tokio::time::Instant::now();
+    for _ in 0..5 {
+        limiter.acquire().await;
+    }
+    assert_eq!(start.elapsed(), Duration::from_secs(30));
+    assert_eq!(limiter.available_tokens(), 0);
+
+    tokio::time::sleep(Duration::from_secs(3600)).await;
+    assert_eq!(limiter.available_tokens(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn leaky_bucket_spaces_operations() {
+    install();
+    let bucket = Arc::new(LeakyBucket::new(Duration::from_secs(10)));
+
+    let start = tokio::time::Instant::now();
+    let tasks: Vec<_> = (0..4)
+        .map(|_| {
+            let bucket = bucket.clone();
+            tokio::spawn(async move {
+                bucket.acquire().await;
+                start.elapsed()
+            })
+        })
+        .collect();
+    let mut times = Vec::new();
+    for task in tasks {
+        times.push(task.await.unwrap());
+    }
+    times.sort();
+    assert_eq!(times, [0, 10, 20, 30].map(Duration::from_secs));
+
+    tokio::time::sleep(Duration::from_secs(3600)).await;
+    assert!(bucket.try_acquire().is_ok());
+    assert!(bucket.try_acquire().is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn sliding_window_counts_weights() {
+    install();
+    let limiter = SlidingWindow::new(10, Duration::from_secs(60));
+
+    let start = tokio::time::Instant::now();
+    limiter.acquire_many(6).await;
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    limiter.acquire_many(4).await;
+
+    // Waits for the first operation to leave the window.
+    limiter.acquire_many(5).await;
+    assert_eq!(start.elapsed(), Duration::from_secs(60));
+    assert_eq!(limiter.available(), 1);
+
+    // Then for the second.
+    limiter.acquire_many(4).await;
+    assert_eq!(start.elapsed(), Duration::from_secs(90));
+}
+
+#[tokio::test(start_paused = true)]
+async fn limiter_takes_tokens_after_slots() {
+    install();
+    let limiter = Arc::new(Limiter::new(
+        1,
+        RateLimiter::new(Duration::from_secs(10), 1),
+    ));
+
+    let start = tokio::time::Instant::now();
+    let running = limiter.acquire().await.unwrap();
+    let queued = tokio::spawn({
+        let limiter = limiter.clone();
+        async move {
+            let _permit = limiter.acquire().await.unwrap();
+            start.elapsed()
+        }
+    });
+
+    // The queued task holds no token while it waits for the slot, so the
+    // bucket refills in the meantime.
+    tokio::time::sleep(Duration::from_secs(25)).await;
+    assert_eq!(limiter.rate_limiter().available_tokens(), 1);
+    drop(running);
+    assert_eq!(queued.await.unwrap(), Duration::from_secs(25));
+}
+
+#[tokio::test(start_paused = true)]
+async fn circuit_breaker_reopens_after_failed_probe() {
+    install();
+    let breaker = CircuitBreaker::new(1, 1, Duration::from_secs(30));
+
+    breaker.record_outcome(Outcome::Overload);
+    tokio::time::sleep(Duration::from_secs(29)).await;
+    assert_eq!(breaker.acquire().await.unwrap_err(), AcquireError::Rejected);
+
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let probe = breaker.acquire().await.unwrap();
+    probe.record_outcome(Outcome::Overload);
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    assert!(breaker.acquire().await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn circuit_breaker_fails_a_probe_without_outcome() {
+    install();
+    let breaker = CircuitBreaker::new(1, 1, Duration::from_secs(30));
+
+    breaker.record_outcome(Outcome::Overload);
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    drop(breaker.acquire().await.unwrap());
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    assert_eq!(breaker.acquire().await.unwrap_err(), AcquireError::Rejected);
+
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert_eq!(breaker.acquire().await.unwrap_err(), AcquireError::Rejected);
+
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    assert!(breaker.acquire().await.is_ok());
+}
+
+#[tokio::test(start_paused = true)]
+async fn circuit_breaker_ignores_a_stale_success() {
+    install();
+    let breaker = CircuitBreaker::new(2, 1, Duration::from_secs(30));
+
+    let slow = breaker.acquire().await.unwrap();
+    breaker
+        .acquire()
+        .await
+        .unwrap()
+        .record_outcome(Outcome::Overload);
+    assert_eq!(breaker.state(), CircuitState::Open);
+
+    // An operation admitted before the breaker opened ends within the cooldown.
+    tokio::time::sleep(Duration::from_secs(10)).await;
+    slow.record_outcome(Outcome::Success);
+    assert_eq!(breaker.state(), CircuitState::Open);
+    assert_eq!(breaker.acquire().await.unwrap_err(), AcquireError::Rejected);
+}
+
+#[tokio::test(start_paused = true)]
+async fn circuit_breaker_closes_only_on_the_probe() {
+    install();
+    let breaker = CircuitBreaker::new(2, 1, Duration::from_secs(30));
+
+    let slow = breaker.acquire().await.unwrap();
+    breaker.record_outcome(Outcome::Overload);
+    tokio::time::sleep(Duration::from_secs(30)).await;
+    let probe = breaker.acquire().await.unwrap();
+    assert!(probe.is_probe());
+    assert!(!slow.is_probe());
+
+    slow.record_outcome(Outcome::Success);
+    breaker.record_outcome(Outcome::Success);
+    assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    assert_eq!(breaker.acquire().await.unwrap_err(), AcquireError::Rejected);
+
+    probe.record_outcome(Outcome::Success);
+    assert_eq!(breaker.state(), CircuitState::Closed);
+}
+
+#[tokio::test(start_paused = true)]
+async fn keyed_semaphore_evicts_idle_keys() {
+    install();
+    let limits = KeyedSemaphore::<u32>::new(1).idle_timeout(Duration::from_secs(60));
+
+    let held = limits.acquire(&1).await.unwrap();
+    drop(limits.acquire(&2).await.unwrap());
+    tokio::time::sleep(Duration::from_secs(60)).await;
+
+    // Adding a key looks for idle ones, but the held key stays.
+    drop(limits.acquire(&3).await.unwrap());
+    assert!(limits.contains_key(&1));
+    assert!(!limits.contains_key(&2));
+    assert!(limits.try_acquire(&1).is_err());
+
+    drop(held);
+    tokio::time::sleep(Duration::from_secs(60)).await;
+    limits.evict_idle();
+    assert!(limits.is_empty());
+}
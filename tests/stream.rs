@@ -0,0 +1,117 @@
+//! Streams and sinks over semaphores: the adapters end with their source and
+//! hand back unused permits, and watches see every change.
+
+use async_sema::stream::LimitedFuturesUnordered;
+use async_sema::{Semaphore, SinkExt as _, StreamExt as _};
+use futures::{stream, SinkExt, StreamExt};
+use std::time::Duration;
+
+const LIMIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn throttle_by_ends_without_credit() {
+    let credit = Semaphore::new(2);
+    let items: Vec<_> =
+        tokio::time::timeout(LIMIT, stream::iter(1..=2).throttle_by(&credit).collect())
+            .await
+            .expect("the stream waited for credit after its last item");
+    assert_eq!(items, [Ok(1), Ok(2)]);
+    assert_eq!(credit.available_permits(), 0);
+
+    let mut empty = stream::empty::<()>().throttle_by(&credit);
+    assert_eq!(tokio::time::timeout(LIMIT, empty.next()).await, Ok(None));
+}
+
+#[tokio::test]
+async fn throttle_by_waits_for_credit_per_item() {
+    let credit = Semaphore::new(0);
+    let mut items = stream::iter(1..=2).throttle_by(&credit);
+    assert!(futures::poll!(items.next()).is_pending());
+
+    credit.add_permits(1);
+    assert_eq!(items.next().await, Some(Ok(1)));
+    credit.add_permits(2);
+    assert_eq!(items.next().await, Some(Ok(2)));
+
+    // The credit left over is not used up by the end of the stream.
+    assert_eq!(items.next().await, None);
+    assert_eq!(credit.available_permits(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn watch_sees_permits_being_taken() {
+    let s = Semaphore::new(2);
+    let mut watch = s.watch();
+    assert_eq!(watch.next().await, Some(2));
+
+    let watcher = tokio::spawn(async move { watch.next().await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    let _guard = s.try_acquire_upto(1).unwrap();
+    assert_eq!(
+        tokio::time::timeout(LIMIT, watcher).await.unwrap().unwrap(),
+        Some(1)
+    );
+}
+
+#[tokio::test]
+async fn limit_unflushed_flushes_when_it_holds_every_permit() {
+    let s = Semaphore::new(1);
+    let mut sink = Vec::new().limit_unflushed(&s);
+    tokio::time::timeout(LIMIT, async {
+        for n in 0..3 {
+            sink.feed(n).await.unwrap();
+        }
+    })
+    .await
+    .expect("the sink waited for a permit it held itself");
+    assert_eq!(sink.unflushed(), 1);
+    assert_eq!(sink.get_ref(), &[0, 1, 2]);
+
+    sink.close().await.unwrap();
+    assert_eq!(s.available_permits(), 1);
+}
+
+#[tokio::test]
+async fn releases_sees_every_guard_drop() {
+    let s = Semaphore::new(4);
+    let releases = s.releases();
+    let tasks: Vec<_> = (0..64)
+        .map(|_| {
+            let s = s.clone();
+            tokio::spawn(async move { drop(s.acquire().await.unwrap()) })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    drop(s);
+
+    let released: Vec<_> = tokio::time::timeout(LIMIT, releases.collect())
+        .await
+        .expect("the stream did not end with the semaphore");
+    assert_eq!(released, [1; 64]);
+}
+
+#[tokio::test]
+async fn limited_futures_unordered_pushes_past_its_limit() {
+    let s = Semaphore::new(2);
+    let mut set = LimitedFuturesUnordered::new(&s);
+    tokio::time::timeout(LIMIT, async {
+        for n in 0..16 {
+            set.push(async move {
+                tokio::task::yield_now().await;
+                n
+            })
+            .await
+            .unwrap();
+        }
+    })
+    .await
+    .expect("pushing waited for a permit held by the set itself");
+
+    let sum = tokio::time::timeout(LIMIT, set.fold(0, |sum, n| async move { sum + n }))
+        .await
+        .unwrap();
+    assert_eq!(sum, 120);
+    assert_eq!(s.available_permits(), 2);
+}
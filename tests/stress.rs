@@ -0,0 +1,631 @@
+//! Stress tests for races between releasing permits and tasks going to sleep.
+//!
+//! A lost wakeup leaves a task waiting while permits are available, which
+//! shows up here as a test that never finishes within its timeout.
+
+use async_sema::pool::Pool;
+use async_sema::sync::{Barrier, Doorbell, Mutex, Notify, RwLock};
+use async_sema::{
+    channel, BinarySemaphore, HierarchicalSemaphore, OwnerSemaphore, Permits, Semaphore, TaskGroup,
+};
+use std::future::poll_fn;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TASKS: usize = 16;
+const ROUNDS: usize = 2_000;
+const LIMIT: Duration = Duration::from_secs(30);
+
+async fn hammer(s: Semaphore, count: Permits) {
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let s = s.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let permit = s.acquire_many(count).await.unwrap();
+                    tokio::task::yield_now().await;
+                    drop(permit);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a waiter missed its wakeup")
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn single_permit() {
+    let s = Semaphore::new(1);
+    hammer(s.clone(), 1).await;
+    assert_eq!(s.available_permits(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn batch_permits() {
+    let s = Semaphore::new(3);
+    hammer(s.clone(), 2).await;
+    assert_eq!(s.available_permits(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn fair_batch_permits() {
+    let s = Semaphore::builder().permits(3).fair(true).build();
+    hammer(s.clone(), 2).await;
+    assert_eq!(s.available_permits(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn cancelled_waiters() {
+    let s = Semaphore::new(2);
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let s = s.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    // Some waiters give up early, which must not strand the permit.
+                    let acquire = s.acquire_many(1 + (i % 2) as Permits);
+                    if let Ok(permit) =
+                        tokio::time::timeout(Duration::from_micros(50), acquire).await
+                    {
+                        tokio::task::yield_now().await;
+                        drop(permit);
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a waiter missed its wakeup")
+            .unwrap();
+    }
+    drop(s.acquire_many(2).await.unwrap());
+    assert_eq!(s.num_waiters(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn polling_tasks() {
+    let s = Semaphore::new(2);
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let s = s.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let permit = poll_fn(|cx| s.poll_acquire(cx, 1)).await.unwrap();
+                    tokio::task::yield_now().await;
+                    drop(permit);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a poller missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(s.available_permits(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn binary_semaphore() {
+    let s = Arc::new(BinarySemaphore::new());
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let s = s.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let guard = s.acquire().await;
+                    tokio::task::yield_now().await;
+                    drop(guard);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a waiter missed its wakeup")
+            .unwrap();
+    }
+    assert!(!s.is_locked());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn mutex() {
+    let lock = Arc::new(Mutex::new(0));
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let lock = lock.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let mut guard = lock.lock().await;
+                    let n = *guard;
+                    tokio::task::yield_now().await;
+                    *guard = n + 1;
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a waiter missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(*lock.lock().await, TASKS * ROUNDS);
+}
+
+#[test]
+fn blocking_threads() {
+    let s = Semaphore::new(1);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..ROUNDS {
+                    let permit = s.blocking_acquire().unwrap();
+                    std::thread::yield_now();
+                    drop(permit);
+                }
+            });
+        }
+    });
+    assert_eq!(s.available_permits(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn large_request_not_starved() {
+    let s = Semaphore::new(2);
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // More small tasks than permits, so a freed permit is always wanted.
+    let small: Vec<_> = (0..8)
+        .map(|_| {
+            let s = s.clone();
+            let stop = stop.clone();
+            tokio::spawn(async move {
+                while !stop.load(Ordering::Relaxed) {
+                    let permit = s.acquire().await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    drop(permit);
+                }
+            })
+        })
+        .collect();
+    while s.num_waiters() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    let permit = tokio::time::timeout(LIMIT, s.acquire_many(2))
+        .await
+        .expect("the large request was starved")
+        .unwrap();
+    drop(permit);
+
+    stop.store(true, Ordering::Relaxed);
+    for task in small {
+        task.await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn rwlock() {
+    let lock = Arc::new(RwLock::new((0, 0)));
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let lock = lock.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    if i % 4 == 0 {
+                        let mut guard = lock.write().await;
+                        guard.0 += 1;
+                        tokio::task::yield_now().await;
+                        guard.1 += 1;
+                    } else {
+                        let guard = lock.read().await;
+                        tokio::task::yield_now().await;
+                        assert_eq!(guard.0, guard.1, "a reader saw a partial write");
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a waiter missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(*lock.read().await, (TASKS / 4 * ROUNDS, TASKS / 4 * ROUNDS));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn barrier() {
+    let barrier = Arc::new(Barrier::new(TASKS));
+    let phase = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let (barrier, phase) = (barrier.clone(), phase.clone());
+            tokio::spawn(async move {
+                for round in 0..ROUNDS / 10 {
+                    assert_eq!(phase.load(Ordering::SeqCst) / TASKS, round);
+                    barrier.wait().await;
+                    phase.fetch_add(1, Ordering::SeqCst);
+                    barrier.wait().await;
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a waiter missed its wakeup")
+            .unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn notify_ping_pong() {
+    let (ping, pong) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+    let task = tokio::spawn({
+        let (ping, pong) = (ping.clone(), pong.clone());
+        async move {
+            for _ in 0..ROUNDS {
+                ping.notified().await;
+                pong.notify_one();
+            }
+        }
+    });
+
+    for _ in 0..ROUNDS {
+        ping.notify_one();
+        tokio::time::timeout(LIMIT, pong.notified())
+            .await
+            .expect("a waiter missed its wakeup");
+    }
+    task.await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn doorbell_wakes_for_every_item() {
+    let bell = Arc::new(Doorbell::new());
+    let queued = Arc::new(AtomicUsize::new(0));
+    let consumer = tokio::spawn({
+        let (bell, queued) = (bell.clone(), queued.clone());
+        async move {
+            let mut seen = 0;
+            while seen < ROUNDS {
+                bell.wait().await;
+                seen += queued.swap(0, Ordering::SeqCst);
+            }
+        }
+    });
+
+    for _ in 0..ROUNDS {
+        queued.fetch_add(1, Ordering::SeqCst);
+        bell.ring();
+        tokio::task::yield_now().await;
+    }
+    tokio::time::timeout(LIMIT, consumer)
+        .await
+        .expect("the consumer missed a ring")
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn channel_many_to_many() {
+    let (tx, rx) = channel::bounded(4);
+    let producers: Vec<_> = (0..TASKS / 2)
+        .map(|_| {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                for i in 0..ROUNDS {
+                    tx.send(i).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+    let consumers: Vec<_> = (0..TASKS / 2)
+        .map(|_| {
+            let rx = rx.clone();
+            tokio::spawn(async move {
+                let mut sum = 0;
+                while let Ok(i) = rx.recv().await {
+                    sum += i;
+                }
+                sum
+            })
+        })
+        .collect();
+
+    for task in producers {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a sender missed its wakeup")
+            .unwrap();
+    }
+    let mut sum = 0;
+    for task in consumers {
+        sum += tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a receiver missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(sum, TASKS / 2 * ROUNDS * (ROUNDS - 1) / 2);
+    assert!(rx.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn hierarchy_respects_every_level() {
+    let root = HierarchicalSemaphore::root(3);
+    let children: Vec<_> = (0..4).map(|_| root.child(2)).collect();
+    let in_flight = Arc::new(AtomicUsize::new(0));
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let child = children[i % children.len()].clone();
+            let in_flight = in_flight.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let permit = child.acquire().await.unwrap();
+                    assert!(in_flight.fetch_add(1, Ordering::SeqCst) < 3);
+                    tokio::task::yield_now().await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    drop(permit);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a task missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(root.available_permits(), 3);
+    for child in &children {
+        assert_eq!(child.semaphore().available_permits(), 2);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn owner_caps_hold_under_contention() {
+    let s = Arc::new(OwnerSemaphore::new(8, 3));
+    let held: Arc<Vec<AtomicUsize>> = Arc::new((0..4).map(|_| AtomicUsize::new(0)).collect());
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let (s, held) = (s.clone(), held.clone());
+            tokio::spawn(async move {
+                let owner = i % held.len();
+                for _ in 0..ROUNDS {
+                    let permit = s.acquire_as(owner, 1).await.unwrap();
+                    assert!(held[owner].fetch_add(1, Ordering::SeqCst) < 3);
+                    tokio::task::yield_now().await;
+                    held[owner].fetch_sub(1, Ordering::SeqCst);
+                    drop(permit);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a task missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(s.semaphore().available_permits(), 8);
+    assert_eq!(s.held_by(&0), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn acquire_all_in_opposite_orders() {
+    let (a, b) = (Semaphore::new(1), Semaphore::new(1));
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let (a, b) = (a.clone(), b.clone());
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    // Acquiring one after the other in these orders would deadlock.
+                    let requests = if i % 2 == 0 {
+                        [(&a, 1), (&b, 1)]
+                    } else {
+                        [(&b, 1), (&a, 1)]
+                    };
+                    let permit = async_sema::acquire_all(&requests).await.unwrap();
+                    tokio::task::yield_now().await;
+                    drop(permit);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a task missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(a.available_permits(), 1);
+    assert_eq!(b.available_permits(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn pool_hands_each_item_to_one_task() {
+    let pool = Arc::new(Pool::from_items((0..3).map(|_| AtomicBool::new(false))));
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let item = pool.get().await;
+                    assert!(!item.swap(true, Ordering::SeqCst));
+                    tokio::task::yield_now().await;
+                    item.store(false, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a task missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(pool.available(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn lazy_pool_creates_at_most_its_maximum() {
+    let created = Arc::new(AtomicUsize::new(0));
+    let pool = Arc::new(Pool::new(3, {
+        let created = created.clone();
+        move || {
+            let created = created.clone();
+            async move {
+                created.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                AtomicBool::new(false)
+            }
+        }
+    }));
+
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|_| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                for _ in 0..ROUNDS {
+                    let item = pool.get().await;
+                    assert!(!item.swap(true, Ordering::SeqCst));
+                    tokio::task::yield_now().await;
+                    item.store(false, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a task missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(created.load(Ordering::SeqCst), 3);
+    assert_eq!(pool.available(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn map_concurrent_limited_shares_the_limit() {
+    use async_sema::StreamExt as _;
+    use futures::StreamExt;
+
+    let s = Arc::new(Semaphore::new(3));
+    let running = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..TASKS / 4)
+        .map(|_| {
+            let (s, running) = (s.clone(), running.clone());
+            tokio::spawn(async move {
+                let outputs = futures::stream::iter(0..ROUNDS)
+                    .map_concurrent_limited(&s, |i| {
+                        let running = running.clone();
+                        async move {
+                            assert!(running.fetch_add(1, Ordering::SeqCst) < 3);
+                            tokio::task::yield_now().await;
+                            running.fetch_sub(1, Ordering::SeqCst);
+                            i
+                        }
+                    })
+                    .map(Result::unwrap)
+                    .fold(0, |sum, i| async move { sum + i })
+                    .await;
+                assert_eq!(outputs, ROUNDS * (ROUNDS - 1) / 2);
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("a stream missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(s.available_permits(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn acquire_all_queues_behind_fair_waiters() {
+    let a = Semaphore::builder().permits(2).fair(true).build();
+    let b = Semaphore::new(1);
+    let root = HierarchicalSemaphore::with_semaphore(a.clone());
+    let child = root.child(1);
+
+    let held = a.acquire().await.unwrap();
+    let queued = tokio::spawn({
+        let a = a.clone();
+        async move { drop(a.acquire_many(2).await.unwrap()) }
+    });
+    while a.num_waiters() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    // A permit of `a` is free, but the fair queue keeps it for the waiter.
+    let all = tokio::spawn({
+        let (a, b) = (a.clone(), b.clone());
+        async move { drop(async_sema::acquire_all(&[(&a, 1), (&b, 1)]).await.unwrap()) }
+    });
+    let nested = tokio::spawn(async move { drop(child.acquire().await.unwrap()) });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!all.is_finished() && !nested.is_finished());
+
+    drop(held);
+    for task in [queued, all, nested] {
+        tokio::time::timeout(LIMIT, task)
+            .await
+            .expect("an acquire spun or missed its wakeup")
+            .unwrap();
+    }
+    assert_eq!(a.available_permits(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn task_group_joins_every_child() {
+    let group = TaskGroup::new(3);
+    let running = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    for _ in 0..ROUNDS / 10 {
+        for _ in 0..TASKS {
+            let (running, done) = (running.clone(), done.clone());
+            tokio::spawn(group.run(async move {
+                assert!(running.fetch_add(1, Ordering::SeqCst) < 3);
+                tokio::task::yield_now().await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        tokio::time::timeout(LIMIT, group.join())
+            .await
+            .expect("the group missed a child finishing");
+    }
+    assert_eq!(done.load(Ordering::SeqCst), ROUNDS / 10 * TASKS);
+    assert_eq!(group.semaphore().available_permits(), 3);
+}
@@ -0,0 +1,133 @@
+//! The tokio integration: spawned tasks give their permits back however
+//! they end, timeouts follow tokio's clock, and blocking acquires hand off
+//! the worker they block.
+//!
+//! Run with `cargo test --features tokio`.
+
+#![cfg(feature = "tokio")]
+
+use async_sema::{AcquireError, LimitedJoinSet, Semaphore};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn spawn_limited_releases_on_panic_and_abort() {
+    let s = Semaphore::new(2);
+
+    let panicked = s.spawn_limited(async { panic!("boom") }).await.unwrap();
+    assert!(panicked.await.unwrap_err().is_panic());
+    assert_eq!(s.available_permits(), 2);
+
+    let aborted = s
+        .spawn_limited(tokio::time::sleep(Duration::from_secs(60)))
+        .await
+        .unwrap();
+    assert_eq!(s.available_permits(), 1);
+    aborted.abort();
+    assert!(aborted.await.unwrap_err().is_cancelled());
+    assert_eq!(s.available_permits(), 2);
+}
+
+#[tokio::test]
+async fn spawn_limited_waits_for_a_permit() {
+    let s = Semaphore::new(1);
+    let held = s.acquire().await.unwrap();
+
+    let spawn = s.spawn_limited(async {});
+    tokio::pin!(spawn);
+    assert!(futures::poll!(spawn.as_mut()).is_pending());
+
+    drop(held);
+    spawn.await.unwrap().await.unwrap();
+    assert_eq!(s.available_permits(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn limited_join_set_caps_running_tasks() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let mut set = LimitedJoinSet::new(3);
+
+    for _ in 0..32 {
+        let (running, peak) = (running.clone(), peak.clone());
+        set.spawn(async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        })
+        .await
+        .unwrap();
+    }
+    while let Some(joined) = set.join_next().await {
+        joined.unwrap();
+    }
+    assert!(peak.load(Ordering::SeqCst) <= 3);
+    assert_eq!(set.semaphore().available_permits(), 3);
+}
+
+#[tokio::test(start_paused = true)]
+async fn acquire_timeout_follows_paused_time() {
+    let s = Semaphore::new(0);
+    let started = tokio::time::Instant::now();
+    let real = std::time::Instant::now();
+
+    let timed_out = s.acquire_timeout(1, Duration::from_secs(3600)).await;
+    assert_eq!(timed_out.unwrap_err(), AcquireError::Timeout);
+    assert!(started.elapsed() >= Duration::from_secs(3600));
+    assert!(real.elapsed() < Duration::from_secs(60));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn blocking_acquire_hands_off_the_worker() {
+    let s = Semaphore::new(0);
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    // Both tasks start on the only worker, and the releasing one only runs
+    // once the blocked one has handed the worker off.
+    let blocked = tokio::spawn({
+        let s = s.clone();
+        async move {
+            tx.send(()).unwrap();
+            s.blocking_acquire().unwrap().forget();
+        }
+    });
+    let releasing = tokio::spawn({
+        let s = s.clone();
+        async move {
+            rx.await.unwrap();
+            s.add_permits(1);
+        }
+    });
+    tokio::time::timeout(Duration::from_secs(30), async {
+        blocked.await.unwrap();
+        releasing.await.unwrap();
+    })
+    .await
+    .expect("the blocked task kept the worker");
+}
+
+#[tokio::test]
+async fn spawn_blocking_limited_caps_blocking_work() {
+    let s = Semaphore::new(2);
+    let running = Arc::new(AtomicUsize::new(0));
+
+    let mut tasks = Vec::new();
+    for _ in 0..16 {
+        let running = running.clone();
+        let task = s
+            .spawn_blocking_limited(move || {
+                assert!(running.fetch_add(1, Ordering::SeqCst) < 2);
+                std::thread::sleep(Duration::from_millis(1));
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+            .await
+            .unwrap();
+        tasks.push(task);
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(s.available_permits(), 2);
+}